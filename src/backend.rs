@@ -0,0 +1,133 @@
+//! Backends that [`Outputs`](crate::Outputs) use to talk to the compositor.
+
+use color_eyre::{eyre::eyre, Result};
+use swayipc::Connection;
+
+use crate::outputs::{Mode, Output};
+
+/// A compositor that can report its outputs and run `output` commands.
+pub trait Backend {
+    /// Query all outputs currently known to the compositor.
+    fn outputs(&mut self) -> Result<Vec<Output>>;
+
+    /// Run a single command, e.g. `output eDP-1 disable`.
+    fn run_command(&mut self, payload: &str) -> Result<()>;
+}
+
+/// The default backend talking to sway via its IPC socket.
+pub struct Sway(Connection);
+
+impl Sway {
+    pub fn new() -> Result<Self> {
+        Ok(Self(Connection::new()?))
+    }
+}
+
+impl Backend for Sway {
+    fn outputs(&mut self) -> Result<Vec<Output>> {
+        let raw_outputs = self.0.get_outputs()?;
+
+        let outputs = raw_outputs
+            .into_iter()
+            .map(|o| {
+                let model = o.make + " " + &o.model;
+                // sway reports "Unknown" if the output does not provide a serial
+                let serial = if o.serial == "Unknown" {
+                    String::new()
+                } else {
+                    o.serial
+                };
+                Output::new(o.name, model)
+                    .with_serial(serial)
+                    .with_position((o.rect.x, o.rect.y))
+                    .with_mode(o.current_mode.map(Mode::from))
+                    // sway reports a scale of -1 for disabled outputs
+                    .with_scale(o.scale.filter(|s| *s > 0.0).unwrap_or(1.0))
+                    .with_transform(o.transform.and_then(|t| t.parse().ok()).unwrap_or_default())
+                    .with_enabled(o.active)
+                    .with_modes(o.modes.into_iter().map(Mode::from).collect())
+            })
+            .collect();
+        Ok(outputs)
+    }
+
+    fn run_command(&mut self, payload: &str) -> Result<()> {
+        for outcome in self.0.run_command(payload)? {
+            outcome.map_err(|e| eyre!("Command '{}' failed: {}", payload, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// An in-memory backend with a fixed set of outputs that records every command it receives.
+#[derive(Debug, Clone, Default)]
+pub struct Fake {
+    outputs: Vec<Output>,
+    commands: Vec<String>,
+    failing: Vec<String>,
+}
+
+impl Fake {
+    pub fn new(outputs: Vec<Output>) -> Self {
+        Self {
+            outputs,
+            ..Default::default()
+        }
+    }
+
+    /// Let every command containing `pattern` fail.
+    pub fn fail_on(mut self, pattern: impl Into<String>) -> Self {
+        self.failing.push(pattern.into());
+        self
+    }
+
+    /// All commands received so far, in order.
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+}
+
+impl Backend for Fake {
+    fn outputs(&mut self) -> Result<Vec<Output>> {
+        Ok(self.outputs.clone())
+    }
+
+    fn run_command(&mut self, payload: &str) -> Result<()> {
+        self.commands.push(payload.to_string());
+        match self.failing.iter().find(|p| payload.contains(p.as_str())) {
+            Some(_) => Err(eyre!("Command '{}' failed", payload)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Wraps a backend to query its outputs but only record commands instead of running them.
+pub struct DryRun<B> {
+    inner: B,
+    commands: Vec<String>,
+}
+
+impl<B: Backend> DryRun<B> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            commands: Vec::new(),
+        }
+    }
+
+    /// All commands that would have been run, in order.
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+}
+
+impl<B: Backend> Backend for DryRun<B> {
+    fn outputs(&mut self) -> Result<Vec<Output>> {
+        self.inner.outputs()
+    }
+
+    fn run_command(&mut self, payload: &str) -> Result<()> {
+        self.commands.push(payload.to_string());
+        Ok(())
+    }
+}
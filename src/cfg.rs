@@ -1,58 +1,510 @@
-use color_eyre::{eyre::Context, Result};
+use color_eyre::{
+    eyre::{eyre, Context},
+    Result,
+};
+use indexmap::IndexMap;
 use log::info;
-use serde::{Deserialize, Serialize};
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
 use std::{
-    collections::HashMap,
     ops::Deref,
     path::{Path, PathBuf},
 };
 
-use crate::Outputs;
+use crate::{
+    outputs::{Mode, Output, Transform},
+    Outputs,
+};
+
+mod pattern;
 
-#[derive(Debug, Deserialize)]
-pub struct Cfgs(HashMap<String, Config>);
+pub use pattern::Pattern;
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Default)]
+pub struct Cfgs {
+    /// Configurations in the order of the file
+    configs: IndexMap<String, Config>,
+    /// Global mode policy for configurations without their own
+    mode_policy: Option<ModePolicy>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct DesiredOutput {
-    pub name: String,
+    /// Make and model of the output, may be a glob or a `regex:` pattern
+    #[serde(default, skip_serializing_if = "Pattern::is_empty")]
+    pub name: Pattern,
+    /// Serial number to tell apart identical monitors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    /// Connector the output is plugged into, e.g. `HDMI-A-1`, may be a pattern like `name`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connector: Option<Pattern>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub scale: Option<f64>,
+    /// Mode as `<width>x<height>[@<refresh>]`, e.g. `2560x1440@144`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<ModeRequest>,
+    /// Width of the mode, overrides the width given in `mode`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<i32>,
+    /// Height of the mode, overrides the height given in `mode`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<i32>,
+    /// Refresh rate in Hz, overrides the refresh rate given in `mode`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<f64>,
+    /// Rotation and flipping, `normal` if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<Transform>,
+    /// Absolute position in the layout or side of the preceding output instead of the automatic
+    /// placement
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<OutputPosition>,
+    /// Place left of the output with this name
+    #[serde(default, rename = "left-of", skip_serializing_if = "Option::is_none")]
+    pub left_of: Option<String>,
+    /// Place right of the output with this name
+    #[serde(default, rename = "right-of", skip_serializing_if = "Option::is_none")]
+    pub right_of: Option<String>,
+    /// Place above the output with this name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub above: Option<String>,
+    /// Place below the output with this name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub below: Option<String>,
+    /// Alignment along the edge of the output referenced by `left-of`, `right-of`, `above` or
+    /// `below`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub align: Option<Align>,
+    /// Apply the configuration even if this output is not connected
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub optional: bool,
+    /// Whether the output was given by its name only, to save it the same way
+    #[serde(skip)]
+    pub shorthand: bool,
+}
+
+/// Alignment of an output along an edge; `top` and `left` as well as `bottom` and `right` are
+/// equivalent, whichever reads better.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    #[default]
+    Top,
+    Left,
+    Center,
+    Bottom,
+    Right,
+}
+
+/// Absolute position of an output in the layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Position of an output in a configuration: absolute, or at a side of the preceding output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum OutputPosition {
+    At(Position),
+    Side(Side),
+}
+
+/// Side of the preceding output to place an output at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+    Above,
+    Below,
+}
+
+impl std::fmt::Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Side::Left => "left",
+            Side::Right => "right",
+            Side::Above => "above",
+            Side::Below => "below",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// How to pick the mode of an output if the configuration does not request one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModePolicy {
+    /// Largest resolution, sway picks the refresh rate
+    #[default]
+    Largest,
+    /// Highest refresh rate, the largest resolution among those
+    HighestRefresh,
+    /// Largest resolution, the highest refresh rate among those
+    LargestThenRefresh,
+}
+
+impl std::fmt::Display for ModePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ModePolicy::Largest => "largest",
+            ModePolicy::HighestRefresh => "highest-refresh",
+            ModePolicy::LargestThenRefresh => "largest-then-refresh",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::fmt::Display for Align {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Align::Top => "top",
+            Align::Left => "left",
+            Align::Center => "center",
+            Align::Bottom => "bottom",
+            Align::Right => "right",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::fmt::Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Layout::Horizontal => "horizontal",
+            Layout::Vertical => "vertical",
+            Layout::Grid => "grid",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A requested mode where unset parts are not constrained.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ModeRequest {
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Refresh rate in Hz
+    pub refresh: Option<f64>,
+}
+
+impl ModeRequest {
+    /// Whether `mode` satisfies the request. Refresh rates match if they differ by less than
+    /// half a Hz, e.g. 144 matches 143.998 Hz.
+    pub fn matches(&self, mode: &Mode) -> bool {
+        self.width.is_none_or(|w| w == mode.width)
+            && self.height.is_none_or(|h| h == mode.height)
+            && self
+                .refresh
+                .is_none_or(|r| (mode.refresh as f64 / 1000.0 - r).abs() < 0.5)
+    }
+}
+
+impl std::str::FromStr for ModeRequest {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || eyre!("Invalid mode '{}', expected e.g. '2560x1440@144'", s);
+        let (size, refresh) = match s.split_once('@') {
+            Some((size, refresh)) => (size, Some(refresh)),
+            None => (s, None),
+        };
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+        let refresh = refresh
+            .map(|r| r.trim().trim_end_matches("Hz").trim().parse::<f64>())
+            .transpose()
+            .map_err(|_| invalid())?;
+        Ok(Self {
+            width: Some(width.trim().parse().map_err(|_| invalid())?),
+            height: Some(height.trim().parse().map_err(|_| invalid())?),
+            refresh,
+        })
+    }
+}
+
+impl TryFrom<String> for ModeRequest {
+    type Error = color_eyre::Report;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<ModeRequest> for String {
+    fn from(mode: ModeRequest) -> Self {
+        mode.to_string()
+    }
+}
+
+impl std::fmt::Display for ModeRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dim = |d: Option<i32>| d.map(|d| d.to_string()).unwrap_or("*".to_string());
+        write!(f, "{}x{}", dim(self.width), dim(self.height))?;
+        if let Some(refresh) = self.refresh {
+            write!(f, "@{}", refresh)?;
+        }
+        Ok(())
+    }
+}
+
+impl DesiredOutput {
+    /// Identifier used in messages: the name, or the connector without name, plus the serial, if
+    /// any.
+    pub fn id(&self) -> String {
+        let name = match &self.connector {
+            Some(connector) if self.name.is_empty() => connector,
+            _ => &self.name,
+        };
+        match &self.serial {
+            Some(serial) => format!("{} [{}]", name, serial),
+            None => name.to_string(),
+        }
+    }
+
+    /// Check that the entry identifies an output and has a usable scale. A catch-all entry may
+    /// stand for several outputs, so it can only be placed next to the preceding output.
+    pub fn validate(&self) -> Result<()> {
+        let relative = [&self.left_of, &self.right_of, &self.above, &self.below]
+            .iter()
+            .any(|r| r.is_some());
+        match (&self.name, &self.connector) {
+            (name, None) if name.is_empty() => Err(eyre!("Output needs a name or a connector")),
+            _ if self.scale.is_some_and(|s| !s.is_finite() || s <= 0.0) => {
+                Err(eyre!("scale must be a positive number"))
+            }
+            _ if self.is_catch_all()
+                && (relative || matches!(self.position, Some(OutputPosition::At(_)))) =>
+            {
+                Err(eyre!(
+                    "Output '*' can only be placed next to the preceding output, e.g. position = \"right\""
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the entry is named `*` without further restrictions and takes any number of
+    /// outputs not matched by other entries.
+    pub fn is_catch_all(&self) -> bool {
+        self.name.as_str() == "*" && self.connector.is_none() && self.serial.is_none()
+    }
+
+    /// Whether `reference` of a relative placement refers to this entry.
+    pub fn is_referenced_by(&self, reference: &str) -> bool {
+        self.name.as_str() == reference
+            || self.id() == reference
+            || self
+                .connector
+                .as_ref()
+                .is_some_and(|c| c.as_str() == reference)
+    }
+
+    /// Whether the entry names its output exactly instead of by a pattern.
+    pub fn is_exact(&self) -> bool {
+        self.name.is_exact() && self.connector.as_ref().is_none_or(Pattern::is_exact)
+    }
+
+    /// The requested mode combining `mode` with `width`, `height` and `refresh`, if any is set.
+    pub fn mode_request(&self) -> Option<ModeRequest> {
+        if self.mode.is_none()
+            && self.width.is_none()
+            && self.height.is_none()
+            && self.refresh.is_none()
+        {
+            return None;
+        }
+        let mode = self.mode.unwrap_or_default();
+        Some(ModeRequest {
+            width: self.width.or(mode.width),
+            height: self.height.or(mode.height),
+            refresh: self.refresh.or(mode.refresh),
+        })
+    }
+
+    /// Whether the connected `output` satisfies this entry.
+    pub fn matches(&self, output: &Output) -> bool {
+        (self.name.is_empty() || self.name.matches(output.model()))
+            && self
+                .connector
+                .as_ref()
+                .is_none_or(|p| p.matches(output.name()))
+            && self
+                .serial
+                .as_ref()
+                .is_none_or(|serial| serial == output.serial())
+    }
 }
 
 /// Config describes a named configuration: outputs plus optional priority
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Config {
+    /// Outputs given by name or as table
+    #[serde(deserialize_with = "deserialize_outputs")]
     pub outputs: Vec<DesiredOutput>,
     /// higher number -> higher priority; optional for backwards compatibility
     pub priority: Option<i64>,
+    /// Mode policy for outputs without a requested mode, falls back to the global policy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode_policy: Option<ModePolicy>,
+    /// Arrangement of outputs without explicit placement, horizontal if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Layout>,
+    /// Number of columns of the grid layout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<usize>,
+    /// Alignment of outputs of different sizes within their row or column of the layout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub align: Option<Align>,
+    /// Which connected outputs the daemon requires to use the configuration, `subset` if not given
+    #[serde(default, rename = "match", skip_serializing_if = "Option::is_none")]
+    pub match_mode: Option<MatchMode>,
+}
+
+impl Config {
+    /// Check settings that only make sense together.
+    pub fn validate(&self) -> Result<()> {
+        if self.outputs.iter().filter(|d| d.is_catch_all()).count() > 1 {
+            return Err(eyre!("Only one output '*' is allowed per configuration"));
+        }
+        for desired in &self.outputs {
+            let references = [
+                &desired.left_of,
+                &desired.right_of,
+                &desired.above,
+                &desired.below,
+            ];
+            for reference in references.into_iter().flatten() {
+                if self
+                    .outputs
+                    .iter()
+                    .any(|d| d.is_catch_all() && d.is_referenced_by(reference))
+                {
+                    return Err(eyre!(
+                        "Output '{}' can not be placed relative to '*', it may stand for any number of outputs",
+                        desired.id()
+                    ));
+                }
+            }
+        }
+        match (self.columns, self.layout) {
+            (Some(0), _) => Err(eyre!("columns must be at least 1")),
+            (Some(_), Some(Layout::Grid)) | (None, _) => Ok(()),
+            (Some(_), _) => Err(eyre!("columns requires layout = \"grid\"")),
+        }
+    }
+}
+
+/// Connected outputs required to activate a configuration automatically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    /// All outputs of the configuration are connected, others may be connected as well
+    #[default]
+    Subset,
+    /// Exactly the outputs of the configuration are connected
+    Exact,
+}
+
+impl std::fmt::Display for MatchMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            MatchMode::Subset => "subset",
+            MatchMode::Exact => "exact",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Arrangement of the outputs of a configuration in the order they are listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    #[default]
+    Horizontal,
+    Vertical,
+    Grid,
 }
 
 impl Deref for Cfgs {
-    type Target = HashMap<String, Config>;
+    type Target = IndexMap<String, Config>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.configs
     }
 }
 
-// TODO: allow name + scale and name only
-// #[derive(Debug, Deserialize)]
-// enum OutputVariants {
-//     Full(DesiredOutput),
-//     Name(String),
-// }
+/// An output of a configuration, either given by its name only or as a table.
+struct OutputVariants(DesiredOutput);
+
+impl<'de> Deserialize<'de> for OutputVariants {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = OutputVariants;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a name or a table describing an output")
+            }
+
+            fn visit_str<E: serde::de::Error>(
+                self,
+                name: &str,
+            ) -> std::result::Result<Self::Value, E> {
+                Ok(OutputVariants(DesiredOutput {
+                    name: name.parse().map_err(E::custom)?,
+                    shorthand: true,
+                    ..Default::default()
+                }))
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                // deserializing the table directly keeps the errors of its fields
+                let deserializer = serde::de::value::MapAccessDeserializer::new(map);
+                DesiredOutput::deserialize(deserializer).map(OutputVariants)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+fn deserialize_outputs<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<DesiredOutput>, D::Error> {
+    let outputs = Vec::<OutputVariants>::deserialize(deserializer)?;
+    Ok(outputs.into_iter().map(|o| o.0).collect())
+}
 
 impl TryFrom<&toml_edit::Table> for Cfgs {
     type Error = color_eyre::Report;
 
     fn try_from(table: &toml_edit::Table) -> std::result::Result<Self, Self::Error> {
-        let cfg: Result<HashMap<String, Config>> = table
+        // top level values are global settings, tables are configurations
+        let mode_policy = match table.get("mode_policy").and_then(|i| i.as_value()) {
+            Some(value) => Some(
+                ModePolicy::deserialize(value.clone().into_deserializer())
+                    .wrap_err_with(|| format!("Invalid global mode_policy: {}", value))?,
+            ),
+            None => None,
+        };
+        let cfg: Result<IndexMap<String, Config>> = table
             .into_iter()
+            .filter(|(name, inner)| !(*name == "mode_policy" && inner.is_value()))
             .map(|(name, inner)| {
                 let section_str = inner
                     .as_table()
                     .map(|t| t.to_string())
                     .unwrap_or(inner.as_str().unwrap_or("").to_string());
-                let cfg_entry: Config =
+                let mut cfg_entry: Config =
                     toml_edit::de::from_str(&section_str).wrap_err_with(|| {
                         format!(
                             "Missing outputs in configuration {}: {}",
@@ -60,11 +512,23 @@ impl TryFrom<&toml_edit::Table> for Cfgs {
                             &inner.to_string(),
                         )
                     })?;
+                cfg_entry
+                    .validate()
+                    .wrap_err_with(|| format!("Invalid configuration {}", &name))?;
+                for output in &cfg_entry.outputs {
+                    output.validate().wrap_err_with(|| {
+                        format!("Invalid output in configuration {}: {}", &name, output)
+                    })?;
+                }
+                cfg_entry.mode_policy = cfg_entry.mode_policy.or(mode_policy);
                 let name = name.to_string();
                 Ok((name, cfg_entry))
             })
             .collect();
-        Ok(Cfgs(cfg?))
+        Ok(Cfgs {
+            configs: cfg?,
+            mode_policy,
+        })
     }
 }
 
@@ -81,7 +545,7 @@ impl Cfgs {
 
     /// Return the Config for a named configuration (if present)
     pub fn find(&self, key: &str) -> Option<&Config> {
-        self.0.get(key)
+        self.configs.get(key)
     }
 
     pub fn default_path() -> PathBuf {
@@ -94,17 +558,28 @@ impl Cfgs {
         let active_outputs: Vec<_> = outputs
             .iter()
             .filter(|o| o.enabled())
-            .map(|o| DesiredOutput {
-                name: o.name().to_string(),
-                scale: Some(o.scale()),
+            .map(|o| {
+                // only pin the serial if another connected output has the same model
+                let ambiguous = outputs
+                    .iter()
+                    .any(|other| other.model() == o.model() && other.name() != o.name());
+                Ok(DesiredOutput {
+                    name: o.model().parse()?,
+                    serial: (ambiguous && !o.serial().is_empty()).then(|| o.serial().to_string()),
+                    scale: Some(o.scale()),
+                    transform: Some(o.transform()).filter(|t| *t != Transform::Normal),
+                    ..Default::default()
+                })
             })
-            .collect();
+            .collect::<Result<_>>()?;
 
-        match self.0.insert(
+        match self.configs.insert(
             name.to_string(),
             Config {
                 outputs: active_outputs,
                 priority: None,
+                mode_policy: self.mode_policy,
+                ..Default::default()
             },
         ) {
             Some(_) => info!("Updated config {name}"),
@@ -114,7 +589,7 @@ impl Cfgs {
     }
 
     /// Save the current configurations to the given path, overwriting the file.
-    /// This is a simple overwrite: it serializes the internal HashMap to TOML and writes
+    /// This is a simple overwrite: it serializes the internal map to TOML and writes
     /// it atomically by writing to a temporary file then renaming.
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let path = path.as_ref();
@@ -131,18 +606,25 @@ impl Cfgs {
 
         // For each config, build a table with outputs (array of inline tables) and optional priority,
         // then insert/replace into the document. This preserves other top-level content and comments.
-        for (name, cfg) in &self.0 {
+        if let Some(policy) = self.mode_policy {
+            doc["mode_policy"] = toml_edit::value(policy.to_string());
+        }
+
+        for (name, cfg) in &self.configs {
             let mut section = toml_edit::Table::new();
 
-            // Build outputs array with inline tables
+            // Build outputs array with inline tables, or plain names where the user wrote them
             let mut outputs_array = toml_edit::Array::new();
             for output in &cfg.outputs {
-                let mut output_table = toml_edit::InlineTable::new();
-                output_table.insert("name", output.name.clone().into());
-                if let Some(scale) = output.scale {
-                    output_table.insert("scale", scale.into());
+                let output_table = output
+                    .serialize(toml_edit::ser::ValueSerializer::new())
+                    .wrap_err_with(|| format!("Failed to serialize output {}", output))?;
+                let name_only = output_table.as_inline_table().is_some_and(|t| t.len() == 1);
+                if output.shorthand && name_only {
+                    outputs_array.push(output.name.as_str());
+                } else {
+                    outputs_array.push(output_table);
                 }
-                outputs_array.push(output_table);
             }
             section["outputs"] = toml_edit::Item::Value(toml_edit::Value::Array(outputs_array));
 
@@ -152,6 +634,24 @@ impl Cfgs {
                 // Ensure no stray priority remains if previously present: leave absent.
             }
 
+            // a policy inherited from the global one is not repeated per config
+            if let Some(policy) = cfg.mode_policy.filter(|p| Some(*p) != self.mode_policy) {
+                section["mode_policy"] = toml_edit::value(policy.to_string());
+            }
+
+            if let Some(layout) = cfg.layout {
+                section["layout"] = toml_edit::value(layout.to_string());
+            }
+            if let Some(columns) = cfg.columns {
+                section["columns"] = toml_edit::value(columns as i64);
+            }
+            if let Some(align) = cfg.align {
+                section["align"] = toml_edit::value(align.to_string());
+            }
+            if let Some(match_mode) = cfg.match_mode {
+                section["match"] = toml_edit::value(match_mode.to_string());
+            }
+
             doc[name.as_str()] = toml_edit::Item::Table(section);
         }
 
@@ -167,9 +667,19 @@ impl Cfgs {
     }
 }
 
+/// Serializes the configurations by name in the order of the file.
+impl Serialize for Cfgs {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_map(&self.configs)
+    }
+}
+
 impl std::fmt::Display for Cfgs {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.iter().try_fold((), |_, (name, cfg)| {
+        self.configs.iter().try_fold((), |_, (name, cfg)| {
             let setup_str = cfg
                 .outputs
                 .iter()
@@ -187,7 +697,36 @@ impl std::fmt::Display for Cfgs {
 
 impl std::fmt::Display for DesiredOutput {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} (scale: {})", self.name, self.scale.unwrap_or(1.0))
+        write!(f, "{} (scale: {}", self.id(), self.scale.unwrap_or(1.0))?;
+        if let Some(connector) = self.connector.as_ref().filter(|_| !self.name.is_empty()) {
+            write!(f, ", connector: {}", connector)?;
+        }
+        if let Some(mode) = self.mode_request() {
+            write!(f, ", mode: {}", mode)?;
+        }
+        if let Some(transform) = self.transform {
+            write!(f, ", transform: {}", transform)?;
+        }
+        match self.position {
+            Some(OutputPosition::At(p)) => write!(f, ", position: {}/{}", p.x, p.y)?,
+            Some(OutputPosition::Side(side)) => write!(f, ", position: {}", side)?,
+            None => {}
+        }
+        let relative = [
+            ("left of", &self.left_of),
+            ("right of", &self.right_of),
+            ("above", &self.above),
+            ("below", &self.below),
+        ];
+        for (direction, reference) in relative {
+            if let Some(reference) = reference {
+                write!(f, ", {} {}", direction, reference)?;
+            }
+        }
+        if self.optional {
+            write!(f, ", optional")?;
+        }
+        write!(f, ")")
     }
 }
 
@@ -207,6 +746,209 @@ mod tests {
         let cfg = cfgs.find("a").expect("config 'a' present");
         assert_eq!(cfg.priority.unwrap(), 5);
         assert_eq!(cfg.outputs.len(), 1);
-        assert_eq!(cfg.outputs[0].name, "Foo");
+        assert_eq!(cfg.outputs[0].name.as_str(), "Foo");
+    }
+
+    #[test]
+    fn parse_mode() {
+        let s = r#"
+        [a]
+        outputs = [{ name = "Foo", mode = "2560x1440@144" }, { name = "Bar", mode = "1920x1080", refresh = 60 }]
+        [b]
+        outputs = [{ name = "Foo", mode = "1920" }]
+        "#;
+        let doc: toml_edit::Document = s.parse().unwrap();
+        let table = doc.as_table();
+        assert!(Cfgs::try_from(table).is_err());
+
+        let doc: toml_edit::Document = s.split("[b]").next().unwrap().parse().unwrap();
+        let cfgs = Cfgs::try_from(doc.as_table()).unwrap();
+        let outputs = &cfgs.find("a").expect("config 'a' present").outputs;
+        assert_eq!(
+            outputs[0].mode_request(),
+            Some(ModeRequest {
+                width: Some(2560),
+                height: Some(1440),
+                refresh: Some(144.0)
+            })
+        );
+        assert_eq!(
+            outputs[1].mode_request().unwrap().to_string(),
+            "1920x1080@60"
+        );
+    }
+
+    #[test]
+    fn parse_mode_policy() {
+        let s = r#"
+        mode_policy = "highest-refresh"
+        [a]
+        outputs = [{ name = "Foo" }]
+        [b]
+        outputs = [{ name = "Foo" }]
+        mode_policy = "largest-then-refresh"
+        "#;
+        let doc: toml_edit::Document = s.parse().unwrap();
+        let cfgs = Cfgs::try_from(doc.as_table()).unwrap();
+        assert_eq!(cfgs.len(), 2);
+        assert_eq!(cfgs["a"].mode_policy, Some(ModePolicy::HighestRefresh));
+        assert_eq!(cfgs["b"].mode_policy, Some(ModePolicy::LargestThenRefresh));
+
+        let doc: toml_edit::Document = "mode_policy = \"fastest\"".parse().unwrap();
+        assert!(Cfgs::try_from(doc.as_table()).is_err());
+    }
+
+    #[test]
+    fn parse_layout() {
+        let s = r#"
+        [wall]
+        outputs = [{ name = "Foo" }, { name = "Foo" }, { name = "Foo" }, { name = "Foo" }]
+        layout = "grid"
+        columns = 2
+        "#;
+        let doc: toml_edit::Document = s.parse().unwrap();
+        let cfgs = Cfgs::try_from(doc.as_table()).unwrap();
+        assert_eq!(cfgs["wall"].layout, Some(Layout::Grid));
+        assert_eq!(cfgs["wall"].columns, Some(2));
+
+        for invalid in [
+            "outputs = [\"Foo\"]\nlayout = \"grid\"\ncolumns = 0",
+            "outputs = [\"Foo\"]\ncolumns = 2",
+            "outputs = [\"Foo\"]\nlayout = \"vertical\"\ncolumns = 2",
+        ] {
+            let doc: toml_edit::Document = format!("[wall]\n{}", invalid).parse().unwrap();
+            assert!(Cfgs::try_from(doc.as_table()).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let s = r#"
+        [a]
+        layout = "vertical"
+        align = "center"
+        match = "exact"
+        outputs = [{ name = "Dell", serial = "A", mode = "1920x1080@60" }, { name = "Dell", serial = "B", scale = 1.5, position = { x = 0, y = 1080 } }]
+        "#;
+        let doc: toml_edit::Document = s.parse().unwrap();
+        let cfgs = Cfgs::try_from(doc.as_table()).unwrap();
+
+        let path = std::env::temp_dir().join(format!("oswo-cfg-{}.toml", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        cfgs.save(&path).unwrap();
+        let saved = Cfgs::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let cfg = saved.find("a").expect("config 'a' present");
+        assert_eq!(cfg.layout, Some(Layout::Vertical));
+        assert_eq!(cfg.align, Some(Align::Center));
+        assert_eq!(cfg.match_mode, Some(MatchMode::Exact));
+        let outputs = &cfg.outputs;
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[0].serial.as_deref(), Some("A"));
+        assert_eq!(outputs[0].scale, None);
+        assert_eq!(outputs[0].mode, Some("1920x1080@60".parse().unwrap()));
+        assert_eq!(outputs[1].serial.as_deref(), Some("B"));
+        assert_eq!(outputs[1].scale, Some(1.5));
+        assert_eq!(
+            outputs[1].position,
+            Some(OutputPosition::At(Position { x: 0, y: 1080 }))
+        );
+    }
+
+    #[test]
+    fn parse_shorthand() {
+        let s = r#"
+        [a]
+        outputs = ["LG Display 0x058B", { name = "Dell", scale = 1.25 }, { name = "Foo" }]
+        [b]
+        outputs = [{ name = "Dell", transform = "45" }]
+        "#;
+        let doc: toml_edit::Document = s.parse().unwrap();
+        let err = Cfgs::try_from(doc.as_table()).unwrap_err();
+        assert!(
+            format!("{:?}", err).contains("unknown variant `45`"),
+            "{:?}",
+            err
+        );
+
+        let doc: toml_edit::Document = s.split("[b]").next().unwrap().parse().unwrap();
+        let cfgs = Cfgs::try_from(doc.as_table()).unwrap();
+        let outputs = &cfgs["a"].outputs;
+        assert_eq!(outputs[0].name.as_str(), "LG Display 0x058B");
+        assert!(outputs[0].shorthand);
+        assert_eq!(outputs[1].scale, Some(1.25));
+        assert!(!outputs[1].shorthand);
+
+        let path = std::env::temp_dir().join(format!("oswo-short-{}.toml", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        cfgs.save(&path).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(
+            saved.contains(
+                r#"outputs = ["LG Display 0x058B", { name = "Dell", scale = 1.25 }, { name = "Foo" }]"#
+            ),
+            "{}",
+            saved
+        );
+    }
+
+    #[test]
+    fn parse_patterns() {
+        let s = r#"
+        [a]
+        outputs = ["Dell Inc. *", { connector = "HDMI-A-1" }, { name = "regex:LG .*", connector = "eDP-?" }]
+        "#;
+        let doc: toml_edit::Document = s.parse().unwrap();
+        let cfgs = Cfgs::try_from(doc.as_table()).unwrap();
+        let outputs = &cfgs["a"].outputs;
+        assert!(!outputs[0].is_exact());
+        assert!(outputs[1].is_exact());
+        assert_eq!(outputs[1].id(), "HDMI-A-1");
+        assert!(!outputs[2].is_exact());
+
+        for invalid in [
+            r#"outputs = [{ scale = 2.0 }]"#,
+            r#"outputs = ["regex:LG ("]"#,
+            r#"outputs = [{ name = "LG", scale = 0.0 }]"#,
+            r#"outputs = [{ name = "LG", scale = -1.5 }]"#,
+            r#"outputs = [{ name = "LG", scale = nan }]"#,
+            r#"outputs = ["LG", { name = "*", left-of = "LG" }]"#,
+            r#"outputs = [{ name = "LG", below = "*" }, { name = "*", position = "right" }]"#,
+            r#"outputs = ["LG", { name = "*", position = "right" }, { name = "*", position = "below" }]"#,
+            r#"outputs = ["LG", { name = "*", position = { x = 0, y = 0 } }]"#,
+        ] {
+            let doc: toml_edit::Document = format!("[a]\n{}", invalid).parse().unwrap();
+            assert!(Cfgs::try_from(doc.as_table()).is_err(), "{}", invalid);
+        }
+        let doc: toml_edit::Document =
+            r#"a.outputs = [{ name = "LG", below = "*" }, { name = "*", position = "right" }]"#
+                .parse()
+                .unwrap();
+        let err = Cfgs::try_from(doc.as_table()).unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "Output 'LG' can not be placed relative to '*', it may stand for any number of outputs"
+        );
+    }
+
+    #[test]
+    fn keep_file_order() {
+        let s = r#"
+        [office]
+        outputs = ["Dell"]
+        [alone]
+        outputs = ["LG"]
+        [home]
+        outputs = ["LG", "Dell"]
+        "#;
+        let doc: toml_edit::Document = s.parse().unwrap();
+        let cfgs = Cfgs::try_from(doc.as_table()).unwrap();
+        let names: Vec<_> = cfgs.keys().collect();
+        assert_eq!(names, ["office", "alone", "home"]);
+        assert!(cfgs
+            .to_string()
+            .starts_with("office:\n  Dell (scale: 1)\nalone:"));
     }
 }
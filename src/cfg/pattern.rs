@@ -0,0 +1,119 @@
+//! Patterns matching names of outputs in configurations.
+
+use color_eyre::{eyre::Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// A literal name, a glob with `*` and `?`, or a regular expression prefixed with `regex:`.
+/// Globs and regular expressions have to match the whole name.
+///
+/// The pattern is parsed once and keeps its source to be written back to configs unchanged.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Pattern {
+    source: String,
+    regex: Option<Regex>,
+}
+
+impl Pattern {
+    /// The pattern as written in the configuration.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.source.is_empty()
+    }
+
+    /// Whether the pattern matches exactly one name.
+    pub fn is_exact(&self) -> bool {
+        self.regex.is_none()
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match &self.regex {
+            None => self.source == name,
+            Some(re) => re.is_match(name),
+        }
+    }
+}
+
+impl std::str::FromStr for Pattern {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let regex = if let Some(re) = s.strip_prefix("regex:") {
+            let re = Regex::new(&format!("^(?:{})$", re))
+                .wrap_err_with(|| format!("Invalid regular expression '{}'", re))?;
+            Some(re)
+        } else if s.contains(['*', '?']) {
+            let glob = s
+                .split('*')
+                .map(|part| {
+                    part.split('?')
+                        .map(regex::escape)
+                        .collect::<Vec<_>>()
+                        .join(".")
+                })
+                .collect::<Vec<_>>()
+                .join(".*");
+            let re = Regex::new(&format!("^{}$", glob))
+                .wrap_err_with(|| format!("Invalid pattern '{}'", s))?;
+            Some(re)
+        } else {
+            None
+        };
+        Ok(Self {
+            source: s.to_string(),
+            regex,
+        })
+    }
+}
+
+impl TryFrom<String> for Pattern {
+    type Error = color_eyre::Report;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Pattern> for String {
+    fn from(pattern: Pattern) -> Self {
+        pattern.source
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_match() {
+        let exact: Pattern = "Dell Inc. DELL P2421".parse().unwrap();
+        assert!(exact.is_exact());
+        assert!(exact.matches("Dell Inc. DELL P2421"));
+        assert!(!exact.matches("Dell Inc. DELL P2421 "));
+
+        let glob: Pattern = "Dell Inc. *".parse().unwrap();
+        assert!(!glob.is_exact());
+        assert!(glob.matches("Dell Inc. DELL P2421"));
+        assert!(!glob.matches("LG Display 0x058B"));
+        let glob: Pattern = "DP-?".parse().unwrap();
+        assert!(glob.matches("DP-1"));
+        assert!(!glob.matches("DP-10"));
+        assert!(!glob.matches("eDP-1"));
+
+        let re: Pattern = "regex:Dell Inc\\. DELL (P2421|U2713HM)".parse().unwrap();
+        assert!(re.matches("Dell Inc. DELL U2713HM"));
+        assert!(!re.matches("Dell Inc. DELL P2723DE"));
+        assert!("regex:Dell (".parse::<Pattern>().is_err());
+        assert_eq!(re.to_string(), "regex:Dell Inc\\. DELL (P2421|U2713HM)");
+    }
+}
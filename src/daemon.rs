@@ -2,10 +2,13 @@ use std::path::PathBuf;
 
 use clap::Parser;
 use color_eyre::{eyre::Context, Result};
-use log::{info, trace};
+use log::{error, info, trace};
 use swayipc::{Connection, Event, EventType};
 
-use liboswo::{Cfgs, Outputs};
+use liboswo::{
+    backend::{DryRun, Sway},
+    Cfgs, Outputs,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -16,6 +19,9 @@ struct Args {
     /// Forward log messages to syslog.
     #[arg(short, long)]
     syslog: bool,
+    /// Print the commands for the currently connected outputs instead of running them and exit.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
 }
 
 fn main() -> Result<()> {
@@ -37,13 +43,24 @@ fn main() -> Result<()> {
     let cfg = args.cfg_file.unwrap_or(Cfgs::default_path());
     let cfgs = Cfgs::from_file(cfg).wrap_err("Failed to load configuration")?;
 
+    if args.dry_run {
+        let mut backend = DryRun::new(Sway::new()?);
+        Outputs::query(&mut backend)?.activate_config(&mut backend, &cfgs)?;
+        backend.commands().iter().for_each(|c| println!("{}", c));
+        return Ok(());
+    }
+
     info!("subscribing to output changes");
     let event_ty = [EventType::Output];
     let connection = Connection::new()?;
     let sub = connection.subscribe(event_ty)?;
 
-    let mut last_outputs = Outputs::list()?;
-    last_outputs.activate_config(&cfgs)?;
+    let mut backend = Sway::new()?;
+    let mut last_outputs = Outputs::query(&mut backend)?;
+    // a broken profile must not stop the daemon, the next output change may apply another one
+    if let Err(e) = last_outputs.activate_config(&mut backend, &cfgs) {
+        error!("failed to activate config: {:?}", e);
+    }
 
     for event in sub {
         let event = event?;
@@ -51,12 +68,14 @@ fn main() -> Result<()> {
 
         match event {
             Event::Output(_) => {
-                let outputs = Outputs::list()?;
+                let outputs = Outputs::query(&mut backend)?;
                 if last_outputs == outputs {
                     trace!("no output changes");
                     continue;
                 }
-                outputs.activate_config(&cfgs)?;
+                if let Err(e) = outputs.activate_config(&mut backend, &cfgs) {
+                    error!("failed to activate config: {:?}", e);
+                }
                 last_outputs = outputs;
             }
             _ => unreachable!("can't receive unsubscribed event"),
@@ -1,7 +1,9 @@
 //! A lib to manage sway outputs.
 
+pub mod backend;
 mod cfg;
 pub mod outputs;
 
+pub use backend::Backend;
 pub use cfg::Cfgs;
 pub use outputs::Outputs;
@@ -1,13 +1,22 @@
-use std::path::PathBuf;
+use std::{
+    io::{BufRead, IsTerminal},
+    path::PathBuf,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
 
 use clap::{Parser, Subcommand};
 use color_eyre::{
     eyre::{self, Context},
-    Result,
+    Result, Section,
 };
 
-use liboswo::{Cfgs, Outputs};
-use log::info;
+use liboswo::{
+    backend::{DryRun, Sway},
+    Backend, Cfgs, Outputs,
+};
+use log::{info, warn};
 
 /// Organise sway outputs (oswo).
 #[derive(Parser, Debug)]
@@ -19,18 +28,31 @@ struct Args {
     /// Verbosity of output
     #[arg(short, long, action = clap::ArgAction::Count, global = true)]
     verbose: u8,
+    /// Print the commands that would be sent to sway instead of running them.
+    #[arg(short = 'n', long, global = true)]
+    dry_run: bool,
+    /// Apply setups even if they disable every output.
+    #[arg(short, long, global = true)]
+    force: bool,
 }
 
 #[derive(Subcommand, Debug)]
 enum Cmds {
     /// Show currently active configuration.
     #[command(alias = "d")]
-    Display,
+    Display {
+        /// Print the outputs as JSON.
+        #[arg(long)]
+        json: bool,
+    },
     /// Manually activate displays. Displays are arranged as order of args, left to right.
     #[command(alias = "s")]
     Set {
         /// Setup of outputs
         setup: Vec<String>,
+        /// Revert to the previous setup unless confirmed within this many seconds.
+        #[arg(long, value_name = "SECONDS")]
+        confirm: Option<u64>,
     },
     /// Activate a pre-defined display configuration.
     #[command(alias = "u")]
@@ -40,13 +62,41 @@ enum Cmds {
         /// Path to toml file containing predefined configurations. [$XDG_CONFIG_DIR/oswo.toml]
         #[arg(short, long)]
         cfg_file: Option<PathBuf>,
+        /// Revert to the previous setup unless confirmed within this many seconds.
+        #[arg(long, value_name = "SECONDS")]
+        confirm: Option<u64>,
+    },
+    /// Show how a pre-defined display configuration differs from the current one.
+    Diff {
+        /// Name of predefined configuration.
+        config: String,
+        /// Path to toml file containing predefined configurations. [$XDG_CONFIG_DIR/oswo.toml]
+        #[arg(short, long)]
+        cfg_file: Option<PathBuf>,
+        /// Print the changes as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show how the daemon ranks the pre-defined configurations for the connected outputs.
+    Explain {
+        /// Path to toml file containing predefined configurations. [$XDG_CONFIG_DIR/oswo.toml]
+        #[arg(short, long)]
+        cfg_file: Option<PathBuf>,
+        /// Print the ranking as JSON.
+        #[arg(long)]
+        json: bool,
     },
+    /// Keep a setup applied with `--confirm`.
+    Confirm,
     /// Print all pre-defined configurations.
     #[command(alias = "p")]
     Print {
         /// Path to toml file containing predefined configurations. [$XDG_CONFIG_DIR/oswo.toml]
         #[arg(short, long)]
         cfg_file: Option<PathBuf>,
+        /// Print the configurations as JSON.
+        #[arg(long)]
+        json: bool,
     },
     /// Add the current configuration to the config file.
     #[command(alias = "a")]
@@ -62,26 +112,93 @@ fn main() -> Result<()> {
     color_eyre::install()?;
     let args = Args::parse();
 
+    // confirming only needs the marker file, not sway
+    if let Cmds::Confirm = args.cmds {
+        return std::fs::write(confirm_marker(), "").wrap_err("Failed to confirm setup");
+    }
+
+    let mut sway = Sway::new()?;
+    if args.dry_run {
+        let mut backend = DryRun::new(sway);
+        run(args, &mut backend)?;
+        backend.commands().iter().for_each(|c| println!("{}", c));
+        Ok(())
+    } else {
+        run(args, &mut sway)
+    }
+}
+
+fn run(args: Args, backend: &mut dyn Backend) -> Result<()> {
     let default_cfg = dirs::config_dir()
         .unwrap_or("/etc/xdg/".into())
         .join("oswo.toml");
-    let outputs = Outputs::list()?;
+    let outputs = Outputs::query(backend)?;
+    // nothing is applied in a dry run, so there is nothing to confirm either
+    let confirm = |timeout: Option<u64>| timeout.filter(|_| !args.dry_run);
     match args.cmds {
-        Cmds::Display if args.verbose == 0 => println!("{}", outputs),
-        Cmds::Display => println!("{:#}", outputs),
-        Cmds::Set { setup } => outputs.set_by_name(&setup)?,
-        Cmds::Use { config, cfg_file } => {
-            let cfg = cfg_file.unwrap_or(default_cfg);
+        Cmds::Display { json: true } => println!("{}", serde_json::to_string(&outputs)?),
+        Cmds::Display { .. } if args.verbose == 0 => println!("{}", outputs),
+        Cmds::Display { .. } => println!("{:#}", outputs),
+        Cmds::Set {
+            ref setup,
+            confirm: timeout,
+        } => {
+            let timeout = confirm(timeout);
+            prepare_confirmation(timeout)?;
+            outputs.set_by_name(backend, setup, args.force)?;
+            await_confirmation(&outputs, backend, timeout)?;
+        }
+        Cmds::Use {
+            ref config,
+            ref cfg_file,
+            confirm: timeout,
+        } => {
+            let timeout = confirm(timeout);
+            let cfg = cfg_file.clone().unwrap_or(default_cfg);
             let cfgs = Cfgs::from_file(cfg).wrap_err("Failed to load configuration")?;
             let cfg_entry = cfgs
-                .find(&config)
+                .find(config)
                 .ok_or_else(|| eyre::eyre!("Found no setup for '{}'", config))?;
-            outputs.set_models(&cfg_entry.outputs)?;
+            prepare_confirmation(timeout)?;
+            outputs.set_models(backend, cfg_entry, args.force)?;
+            await_confirmation(&outputs, backend, timeout)?;
         }
-        Cmds::Print { cfg_file } => {
+        Cmds::Diff {
+            ref config,
+            ref cfg_file,
+            json,
+        } => {
+            let cfg = cfg_file.clone().unwrap_or(default_cfg);
+            let cfgs = Cfgs::from_file(cfg).wrap_err("Failed to load configuration")?;
+            let cfg_entry = cfgs
+                .find(config)
+                .ok_or_else(|| eyre::eyre!("Found no setup for '{}'", config))?;
+            let diff = outputs.diff(cfg_entry)?;
+            if json {
+                println!("{}", serde_json::to_string(&diff)?);
+            } else {
+                diff.iter().for_each(|d| println!("{}", d));
+            }
+        }
+        Cmds::Explain { ref cfg_file, json } => {
+            let cfg = cfg_file.clone().unwrap_or(default_cfg);
+            let cfgs = Cfgs::from_file(cfg).wrap_err("Failed to load configuration")?;
+            let verdicts = outputs.rank(&cfgs);
+            if json {
+                println!("{}", serde_json::to_string(&verdicts)?);
+            } else {
+                verdicts.iter().for_each(|v| println!("{}", v));
+            }
+        }
+        Cmds::Confirm => unreachable!("handled before connecting to sway"),
+        Cmds::Print { cfg_file, json } => {
             let cfg = cfg_file.unwrap_or(default_cfg);
             let cfgs = Cfgs::from_file(cfg).wrap_err("Failed to load configuration")?;
-            println!("{}", cfgs);
+            if json {
+                println!("{}", serde_json::to_string(&cfgs)?);
+            } else {
+                println!("{}", cfgs);
+            }
         }
         Cmds::Add { name, cfg_file } => {
             let cfg = cfg_file.unwrap_or(default_cfg);
@@ -94,3 +211,63 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// File created by `oswo confirm` to keep a setup applied with `--confirm`.
+fn confirm_marker() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("oswo.confirm")
+}
+
+/// Remove a stale confirmation so that only one given after applying the new setup counts.
+fn prepare_confirmation(timeout: Option<u64>) -> Result<()> {
+    let marker = confirm_marker();
+    if timeout.is_some() && marker.exists() {
+        std::fs::remove_file(marker).wrap_err("Failed to remove stale confirmation")?;
+    }
+    Ok(())
+}
+
+/// Wait up to `timeout` seconds for `oswo confirm` or Enter in the terminal, otherwise restore
+/// `previous`.
+fn await_confirmation(
+    previous: &Outputs,
+    backend: &mut dyn Backend,
+    timeout: Option<u64>,
+) -> Result<()> {
+    let Some(timeout) = timeout else {
+        return Ok(());
+    };
+    let (tx, rx) = mpsc::channel();
+    if std::io::stdin().is_terminal() {
+        eprintln!("Keep this setup? Press Enter or run 'oswo confirm' within {timeout}s.");
+        thread::spawn(move || {
+            let mut line = String::new();
+            if std::io::stdin()
+                .lock()
+                .read_line(&mut line)
+                .is_ok_and(|n| n > 0)
+            {
+                let _ = tx.send(());
+            }
+        });
+    }
+
+    let marker = confirm_marker();
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+    while Instant::now() < deadline {
+        if rx.try_recv().is_ok() || marker.exists() {
+            let _ = std::fs::remove_file(&marker);
+            info!("setup confirmed");
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    warn!("setup not confirmed within {}s, reverting", timeout);
+    previous
+        .restore(backend)
+        .wrap_err("Failed to restore the previous output configuration")?;
+    Err(eyre::eyre!("Setup was not confirmed within {}s", timeout)
+        .note("Restored the previous output configuration"))
+}
@@ -3,42 +3,132 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
-use color_eyre::Result;
-use log::{info, trace};
-use swayipc::{Connection, Mode};
+use color_eyre::{eyre::eyre, Result, Section};
+use log::{debug, info, trace};
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
 
-use crate::cfg::{Cfgs, Config, DesiredOutput};
+mod layout;
 
-#[derive(Debug, Clone, Default)]
+use crate::backend::{Backend, Sway};
+use crate::cfg::{
+    Align, Cfgs, Config, DesiredOutput, Layout, MatchMode, ModePolicy, ModeRequest, OutputPosition,
+    Position, Side,
+};
+use layout::{Arrangement, Direction, Node, Placement};
+
+/// A mode supported by an output; the refresh rate is given in mHz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Mode {
+    pub width: i32,
+    pub height: i32,
+    pub refresh: i32,
+}
+
+/// Formats the mode as accepted by `output <name> resolution`; a refresh rate of 0 is omitted
+/// and leaves the choice to sway.
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)?;
+        if self.refresh > 0 {
+            write!(f, "@{}.{:03}Hz", self.refresh / 1000, self.refresh % 1000)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<swayipc::Mode> for Mode {
+    fn from(mode: swayipc::Mode) -> Self {
+        Self {
+            width: mode.width,
+            height: mode.height,
+            refresh: mode.refresh,
+        }
+    }
+}
+
+/// Rotation and flipping of an output as understood by `output <name> transform`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum Transform {
+    #[default]
+    #[serde(rename = "normal")]
+    Normal,
+    #[serde(rename = "90")]
+    Rotate90,
+    #[serde(rename = "180")]
+    Rotate180,
+    #[serde(rename = "270")]
+    Rotate270,
+    #[serde(rename = "flipped")]
+    Flipped,
+    #[serde(rename = "flipped-90")]
+    Flipped90,
+    #[serde(rename = "flipped-180")]
+    Flipped180,
+    #[serde(rename = "flipped-270")]
+    Flipped270,
+}
+
+impl Transform {
+    /// Whether width and height are swapped.
+    pub fn is_rotated(&self) -> bool {
+        matches!(
+            self,
+            Transform::Rotate90
+                | Transform::Rotate270
+                | Transform::Flipped90
+                | Transform::Flipped270
+        )
+    }
+}
+
+impl std::str::FromStr for Transform {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::deserialize(s.into_deserializer())
+            .map_err(|_: serde::de::value::Error| eyre!("Invalid transform '{}'", s))
+    }
+}
+
+impl std::fmt::Display for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Transform::Normal => "normal",
+            Transform::Rotate90 => "90",
+            Transform::Rotate180 => "180",
+            Transform::Rotate270 => "270",
+            Transform::Flipped => "flipped",
+            Transform::Flipped90 => "flipped-90",
+            Transform::Flipped180 => "flipped-180",
+            Transform::Flipped270 => "flipped-270",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Output {
     name: String,
     model: String,
+    serial: String,
+    #[serde(serialize_with = "serialize_position")]
     position: (i32, i32),
-    resolution: (u32, u32),
+    /// Current mode of an active output or the mode to set for a planned one
+    mode: Option<Mode>,
     scale: f64,
+    transform: Transform,
     enabled: bool,
     modes: Vec<Mode>,
 }
 
 impl Output {
-    /// Creates a new [`Output`].
-    fn new(
-        name: String,
-        model: String,
-        position: (i32, i32),
-        resolution: (u32, u32),
-        scale: f64,
-        enabled: bool,
-        modes: Vec<Mode>,
-    ) -> Self {
+    /// Creates a new, disabled [`Output`] without any modes.
+    pub fn new(name: impl Into<String>, model: impl Into<String>) -> Self {
         Self {
-            name,
-            model,
-            position,
-            resolution,
-            scale,
-            enabled,
-            modes,
+            name: name.into(),
+            model: model.into(),
+            scale: 1.0,
+            ..Default::default()
         }
     }
 
@@ -50,6 +140,11 @@ impl Output {
         self.model.as_ref()
     }
 
+    /// The serial number, empty if the output does not report one.
+    pub fn serial(&self) -> &str {
+        self.serial.as_ref()
+    }
+
     pub fn enable(self) -> Self {
         Self {
             enabled: true,
@@ -61,6 +156,33 @@ impl Output {
         Self { scale, ..self }
     }
 
+    pub fn with_serial(self, serial: impl Into<String>) -> Self {
+        Self {
+            serial: serial.into(),
+            ..self
+        }
+    }
+
+    pub fn with_transform(self, transform: Transform) -> Self {
+        Self { transform, ..self }
+    }
+
+    pub fn with_enabled(self, enabled: bool) -> Self {
+        Self { enabled, ..self }
+    }
+
+    pub fn with_position(self, position: (i32, i32)) -> Self {
+        Self { position, ..self }
+    }
+
+    pub fn with_mode(self, mode: Option<Mode>) -> Self {
+        Self { mode, ..self }
+    }
+
+    pub fn with_modes(self, modes: Vec<Mode>) -> Self {
+        Self { modes, ..self }
+    }
+
     pub fn disable(self) -> Self {
         Self {
             enabled: false,
@@ -78,11 +200,17 @@ impl Output {
         });
 
         let details = if verbose {
-            ", modes: ".to_string() + &modes
+            format!(
+                ", transform: {}, serial: {}, modes: {}",
+                self.transform, self.serial, modes
+            )
         } else {
             "".to_string()
         };
-        let resolution = format!("{}x{}", self.resolution.0, self.resolution.1);
+        let resolution = self
+            .mode
+            .map(|m| format!("{}x{}", m.width, m.height))
+            .unwrap_or("0x0".to_string());
         format!(
             "{}:{:0pad$}position: {:4}/{}, resolution: {:>9}, scale: {:1.1}, model: {}{}",
             self.name,
@@ -96,10 +224,61 @@ impl Output {
         )
     }
 
-    pub fn best_mode(&'_ self) -> Option<&'_ Mode> {
-        self.modes
+    /// The mode to use according to `policy` if no mode is requested explicitly.
+    pub fn best_mode(&self, policy: ModePolicy) -> Option<Mode> {
+        match policy {
+            ModePolicy::Largest => self
+                .modes
+                .iter()
+                .max_by_key(|mode| mode.width * mode.height)
+                // leave the refresh rate to sway
+                .map(|m| Mode { refresh: 0, ..*m }),
+            ModePolicy::HighestRefresh => self
+                .modes
+                .iter()
+                .max_by_key(|m| (m.refresh, m.width * m.height))
+                .copied(),
+            ModePolicy::LargestThenRefresh => self
+                .modes
+                .iter()
+                .max_by_key(|m| (m.width * m.height, m.refresh))
+                .copied(),
+        }
+    }
+
+    /// Select the best mode satisfying `request`, or fail listing the available modes.
+    pub fn select_mode(&self, request: &ModeRequest) -> Result<Mode> {
+        let mode = self
+            .modes
             .iter()
-            .max_by_key(|mode| mode.width * mode.height)
+            .filter(|m| request.matches(m))
+            .max_by_key(|m| (m.width * m.height, m.refresh))
+            .ok_or_else(|| {
+                let available = self
+                    .modes
+                    .iter()
+                    .map(|m| m.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                eyre!(
+                    "Mode {} is not available for '{}' ({}), available modes: {}",
+                    request,
+                    self.model,
+                    self.name,
+                    available
+                )
+            })?;
+        // without a requested refresh rate sway picks one for the resolution
+        let refresh = if request.refresh.is_some() {
+            mode.refresh
+        } else {
+            0
+        };
+        Ok(Mode { refresh, ..*mode })
+    }
+
+    pub fn mode(&self) -> Option<Mode> {
+        self.mode
     }
 
     pub fn enabled(&self) -> bool {
@@ -109,6 +288,104 @@ impl Output {
     pub fn scale(&self) -> f64 {
         self.scale
     }
+
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    /// The sway command putting the output into this state.
+    pub fn command(&self) -> String {
+        if !self.enabled {
+            return format!("output {} disable", self.name);
+        }
+        let mode = self
+            .mode
+            .map(|m| format!(" resolution {}", m))
+            .unwrap_or_default();
+        format!(
+            "output {} enable position {} {}{} scale {} transform {}",
+            self.name, self.position.0, self.position.1, mode, self.scale, self.transform
+        )
+    }
+
+    /// Whether this planned state is already in effect for the `current` output.
+    pub fn is_applied(&self, current: &Output) -> bool {
+        self.changes(current).is_empty()
+    }
+
+    /// Properties that differ between the `current` state and this planned one. Properties of
+    /// outputs that stay disabled are ignored, and a mode without refresh rate matches any
+    /// refresh rate as sway picks one anyway.
+    pub fn changes(&self, current: &Output) -> Vec<Change> {
+        let mut changes = Vec::new();
+        let mut push = |property, current, planned| {
+            changes.push(Change {
+                property,
+                current,
+                planned,
+            })
+        };
+        if self.enabled != current.enabled {
+            push(
+                "enabled",
+                Value::Enabled(current.enabled),
+                Value::Enabled(self.enabled),
+            );
+        }
+        if !self.enabled {
+            return changes;
+        }
+        if self.position != current.position {
+            push(
+                "position",
+                Value::Position(current.position),
+                Value::Position(self.position),
+            );
+        }
+        if let Some(planned) = self.mode {
+            let applied = current.mode.is_some_and(|current| {
+                planned.width == current.width
+                    && planned.height == current.height
+                    && (planned.refresh == 0 || planned.refresh == current.refresh)
+            });
+            if !applied {
+                push(
+                    "mode",
+                    Value::Mode(current.mode),
+                    Value::Mode(Some(planned)),
+                );
+            }
+        }
+        if (self.scale - current.scale).abs() >= f64::EPSILON {
+            push(
+                "scale",
+                Value::Scale(current.scale),
+                Value::Scale(self.scale),
+            );
+        }
+        if self.transform != current.transform {
+            push(
+                "transform",
+                Value::Transform(current.transform),
+                Value::Transform(self.transform),
+            );
+        }
+        changes
+    }
+
+    /// Size in the layout, i.e. the size of the mode rotated and divided by the scale.
+    pub fn logical_size(&self) -> (i32, i32) {
+        let mode = self.mode.unwrap_or_default();
+        let (w, h) = if self.transform.is_rotated() {
+            (mode.height, mode.width)
+        } else {
+            (mode.width, mode.height)
+        };
+        (
+            (w as f64 / self.scale) as i32,
+            (h as f64 / self.scale) as i32,
+        )
+    }
 }
 
 impl std::fmt::Display for Output {
@@ -124,49 +401,38 @@ impl PartialOrd for Output {
     }
 }
 
+/// Outputs are identified by make + model and serial. The connector name is the fallback for
+/// identical monitors without serial numbers so that they are still ordered deterministically.
 impl Ord for Output {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.model.cmp(&other.model)
+        self.model
+            .cmp(&other.model)
+            .then_with(|| self.serial.cmp(&other.serial))
+            .then_with(|| self.name.cmp(&other.name))
     }
 }
 
 impl PartialEq for Output {
     fn eq(&self, other: &Self) -> bool {
-        self.model == other.model
+        self.cmp(other).is_eq()
     }
 }
 
 impl Eq for Output {}
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct Outputs(BTreeSet<Output>);
 
 impl Outputs {
+    /// List the outputs of the running sway instance.
     pub fn list() -> Result<Self> {
-        let raw_outputs = Connection::new()?.get_outputs()?;
-
-        let outputs = raw_outputs
-            .iter()
-            .map(|o| {
-                let resolution = o
-                    .current_mode
-                    .map(|m| (m.width as u32, m.height as u32))
-                    .unwrap_or((0, 0));
-                let model = o.make.clone() + " " + &o.model;
-                Output::new(
-                    o.name.clone(),
-                    model,
-                    (o.rect.x, o.rect.y),
-                    resolution,
-                    o.scale.unwrap_or(1.0),
-                    o.active,
-                    o.modes.clone(),
-                )
-            })
-            .collect();
+        Self::query(&mut Sway::new()?)
+    }
 
-        let outputs = Self(outputs);
-        Ok(outputs)
+    /// List the outputs known to the given backend.
+    pub fn query(backend: &mut dyn Backend) -> Result<Self> {
+        let outputs = backend.outputs()?.into_iter().collect();
+        Ok(Self(outputs))
     }
 
     fn longest_name(&self) -> usize {
@@ -175,108 +441,562 @@ impl Outputs {
             .fold(0, |len, output| len.max(output.name.len()))
     }
 
-    pub fn set_models(&self, setup: &[DesiredOutput]) -> Result<()> {
-        let disable: Vec<Output> = self
-            .0
+    /// Assign a distinct connected output to every entry of `setup`, in the order of `setup`.
+    ///
+    /// Outputs taken by one entry are handed over to another entry if that lets both be
+    /// assigned, so the order of overlapping patterns does not matter. Entries naming a serial
+    /// and exact names get the first pick of their outputs. A catch-all entry takes all outputs
+    /// left over, possibly none, and optional entries are left out if their output is not
+    /// connected.
+    pub fn match_setup<'a, 'b>(
+        &'a self,
+        setup: &'b [DesiredOutput],
+    ) -> Result<Vec<(&'b DesiredOutput, &'a Output)>> {
+        let outputs: Vec<&Output> = self.0.iter().collect();
+        let candidates: Vec<Vec<usize>> = setup
             .iter()
-            .filter_map(|o| {
-                if !setup.iter().any(|d| d.name == o.model) {
-                    Some(o.clone().disable())
-                } else {
-                    None
-                }
+            .map(|desired| {
+                (0..outputs.len())
+                    .filter(|o| !desired.is_catch_all() && desired.matches(outputs[*o]))
+                    .collect()
             })
             .collect();
+        // required entries first so that optional ones only get what is left
+        let mut order: Vec<usize> = (0..setup.len())
+            .filter(|i| !setup[*i].is_catch_all())
+            .collect();
+        order.sort_by_key(|i| {
+            let desired = &setup[*i];
+            (
+                desired.optional,
+                desired.serial.is_none(),
+                !desired.is_exact(),
+            )
+        });
 
-        let new_setup: Result<Vec<Output>> = setup
+        // owner[o] is the entry output o is assigned to
+        let mut owner: Vec<Option<usize>> = vec![None; outputs.len()];
+        for i in order {
+            let mut visited = vec![false; outputs.len()];
+            if !assign(i, &candidates, &mut owner, &mut visited) {
+                let desired = &setup[i];
+                if !desired.optional {
+                    return Err(eyre!("Display '{}' is not connected", desired.id()));
+                }
+                debug!("optional '{}' is not connected", desired.id());
+            }
+        }
+        let catch_all = setup.iter().position(|d| d.is_catch_all());
+        Ok(setup
             .iter()
-            .map(|desired| {
-                self.0
+            .enumerate()
+            .flat_map(|(i, desired)| {
+                outputs
                     .iter()
-                    .find(|o| o.model == desired.name)
-                    .ok_or(color_eyre::eyre::eyre!(
-                        "Display '{}' is not connected",
-                        desired.name
-                    ))
-                    .map(|o| o.clone().enable().with_scale(desired.scale.unwrap_or(1.0)))
+                    .zip(&owner)
+                    .filter(move |(_, owner)| match owner {
+                        Some(owner) => *owner == i,
+                        None => catch_all == Some(i),
+                    })
+                    .map(move |(output, _)| (desired, *output))
             })
-            .collect();
-        let new_setup = new_setup?;
-        self.set(new_setup.iter())?;
-        self.set(disable.iter())
+            .collect())
+    }
+
+    pub fn set_models(&self, backend: &mut dyn Backend, cfg: &Config, force: bool) -> Result<()> {
+        let plan = self.plan_models(cfg)?;
+        self.set(backend, plan.iter(), force)
     }
 
-    pub fn set_by_name(&self, setup: &[String]) -> Result<()> {
-        let outputs: Vec<_> = self
+    /// Compare the current state of all outputs with the state `cfg` would produce.
+    pub fn diff(&self, cfg: &Config) -> Result<Vec<OutputDiff>> {
+        let plan = self.plan_models(cfg)?;
+        Ok(plan
+            .into_iter()
+            .filter_map(|planned| {
+                let current = self.0.iter().find(|o| o.name == planned.name)?;
+                Some(OutputDiff {
+                    name: planned.name.clone(),
+                    model: planned.model.clone(),
+                    changes: planned.changes(current),
+                })
+            })
+            .collect())
+    }
+
+    /// The state of every output after applying `cfg`: the outputs of `cfg` in their order,
+    /// followed by all other outputs disabled.
+    fn plan_models(&self, cfg: &Config) -> Result<Vec<Output>> {
+        let policy = cfg.mode_policy.unwrap_or_default();
+        let matched = self.match_setup(&cfg.outputs)?;
+        let disable: Vec<Output> = self
             .0
             .iter()
+            .filter(|o| matched.iter().all(|(_, m)| m != o))
+            .map(|o| o.clone().disable())
+            .collect();
+        let missing: Vec<&DesiredOutput> = cfg
+            .outputs
+            .iter()
+            .filter(|d| matched.iter().all(|(m, _)| !std::ptr::eq(*m, *d)))
+            .collect();
+
+        let new_setup: Result<Vec<Output>> = matched
+            .iter()
+            .map(|&(desired, o)| {
+                let mode = match desired.mode_request() {
+                    Some(request) => Some(o.select_mode(&request)?),
+                    None => o.best_mode(policy),
+                };
+                Ok(o.clone()
+                    .enable()
+                    .with_mode(mode)
+                    .with_scale(desired.scale.unwrap_or(1.0))
+                    .with_transform(desired.transform.unwrap_or_default()))
+            })
+            .collect();
+        let arrangement = match cfg.layout.unwrap_or_default() {
+            Layout::Horizontal => Arrangement::Horizontal,
+            Layout::Vertical => Arrangement::Vertical,
+            Layout::Grid => Arrangement::Grid {
+                // as square as possible by default
+                columns: cfg
+                    .columns
+                    .unwrap_or((matched.len() as f64).sqrt().ceil() as usize),
+            },
+        };
+        let align = cfg.align.unwrap_or_default();
+        let new_setup = arrange(
+            new_setup?,
+            &placements(&matched, &missing)?,
+            arrangement,
+            align,
+        )?;
+        Ok(new_setup.into_iter().chain(disable).collect())
+    }
+
+    pub fn set_by_name(
+        &self,
+        backend: &mut dyn Backend,
+        setup: &[String],
+        force: bool,
+    ) -> Result<()> {
+        if let Some(unknown) = setup.iter().find(|d| self.0.iter().all(|o| o.name != **d)) {
+            let connected: Vec<_> = self.0.iter().map(|o| o.name.as_str()).collect();
+            return Err(eyre!("Display '{}' is not connected", unknown)
+                .note(format!("Connected displays: {}", connected.join(", "))));
+        }
+        // enabled outputs are arranged in the order of `setup`, the rest is disabled afterwards
+        let enable: Vec<_> = setup
+            .iter()
+            .filter_map(|desired| self.0.iter().find(|o| o.name == *desired))
             .map(|o| {
-                if setup.iter().any(|desired| **desired == o.name) {
-                    o.clone().enable()
-                } else {
-                    o.clone().disable()
-                }
+                o.clone()
+                    .enable()
+                    .with_mode(o.best_mode(ModePolicy::default()))
             })
             .collect();
-        self.set(outputs.iter())
+        let enable = arrange(enable, &[], Arrangement::Horizontal, Align::Top)?;
+        let disable = self
+            .0
+            .iter()
+            .filter(|o| !setup.contains(&o.name))
+            .map(|o| o.clone().disable());
+        let outputs: Vec<_> = enable.into_iter().chain(disable).collect();
+        self.set(backend, outputs.iter(), force)
     }
 
-    fn set<'a>(&self, new_setup: impl Iterator<Item = &'a Output>) -> Result<()> {
-        let mut cmd_con = swayipc::Connection::new()?;
-        let mut last_x = 0;
+    /// Run the commands for `new_setup`. If any command fails, the outputs touched so far are
+    /// restored to their state in `self` and the outcome of that is attached to the error.
+    ///
+    /// Outputs already in their planned state are skipped. A setup without any enabled output is
+    /// refused unless `force` is set.
+    fn set<'a>(
+        &self,
+        backend: &mut dyn Backend,
+        new_setup: impl Iterator<Item = &'a Output>,
+        force: bool,
+    ) -> Result<()> {
+        let new_setup: Vec<&Output> = new_setup.collect();
+        if !force && !new_setup.iter().any(|o| o.enabled) {
+            return Err(eyre!("Refusing to disable every output")
+                .suggestion("Pass --force to apply this setup anyway"));
+        }
+        let mut touched: Vec<&Output> = Vec::new();
         for o in new_setup {
-            let payload = if o.enabled {
-                let desired_mode = o.best_mode();
-                let (width, height) = desired_mode.map(|m| (m.width, m.height)).unwrap_or((0, 0));
-                let payload = format!(
-                    "output {} enable position {} 0 resolution {}x{} scale {}",
-                    o.name(),
-                    last_x,
-                    width,
-                    height,
-                    o.scale
-                );
-                let scaled_w = width as f64 / o.scale;
-                last_x += scaled_w as i32;
-                payload
-            } else {
-                format!("output {} disable", o.name())
-            };
-            // println!("{}", payload);
-            cmd_con.run_command(payload)?;
+            if self
+                .0
+                .iter()
+                .any(|current| current.name == o.name && o.is_applied(current))
+            {
+                debug!("{} is already set up, skipping", o.name);
+                continue;
+            }
+            touched.push(o);
+            if let Err(e) = backend.run_command(&o.command()) {
+                let report = e.wrap_err("Failed to apply output configuration");
+                return Err(match self.rollback(backend, &touched) {
+                    Ok(()) => report.note("Restored the previous output configuration"),
+                    Err(rollback) => report.warning(format!(
+                        "Failed to restore the previous output configuration: {}",
+                        rollback
+                    )),
+                });
+            }
         }
 
         Ok(())
     }
 
-    pub fn activate_config(&self, cfgs: &Cfgs) -> Result<()> {
-        let connected_names: BTreeSet<String> =
-            self.iter().map(|o| o.model().to_string()).collect();
-        trace!("connected displays: {:?}", connected_names);
-        // collect configs where all required outputs are connected
-        let mut valid_cfgs: Vec<(&String, &Config)> = Vec::new();
-        for (k, v) in cfgs.iter() {
-            let names: BTreeSet<_> = v.outputs.iter().map(|d| d.name.clone()).collect();
-            if names.is_subset(&connected_names) {
-                valid_cfgs.push((k, v));
-            }
-        }
+    /// Restore all outputs to their state in `self`, e.g. to revert an unconfirmed setup.
+    pub fn restore(&self, backend: &mut dyn Backend) -> Result<()> {
+        let all: Vec<&Output> = self.0.iter().collect();
+        self.rollback(backend, &all)
+    }
 
-        // Sort ascending so last() is the best: priority (higher wins), then number of outputs (bigger wins)
-        valid_cfgs.sort_by(|a, b| {
-            let pa = a.1.priority.unwrap_or(0);
-            let pb = b.1.priority.unwrap_or(0);
-            pa.cmp(&pb).then(a.1.outputs.len().cmp(&b.1.outputs.len()))
-        });
+    /// Restore the `touched` outputs to their state in `self`.
+    fn rollback(&self, backend: &mut dyn Backend, touched: &[&Output]) -> Result<()> {
+        let previous: Vec<&Output> = self
+            .0
+            .iter()
+            .filter(|o| touched.iter().any(|t| t.name == o.name))
+            .collect();
+        // enable outputs first to never end up without an active one
+        let enable = previous.iter().filter(|o| o.enabled);
+        let disable = previous.iter().filter(|o| !o.enabled);
+        let failed: Vec<String> = enable
+            .chain(disable)
+            .filter_map(|o| backend.run_command(&o.command()).err())
+            .map(|e| e.to_string())
+            .collect();
+        if failed.is_empty() {
+            info!("restored {} outputs", previous.len());
+            Ok(())
+        } else {
+            Err(eyre!("{}", failed.join("; ")))
+        }
+    }
 
-        trace!("relevant cfgs: {:?}", valid_cfgs);
-        if let Some((name, best_cfg)) = valid_cfgs.last() {
+    pub fn activate_config(&self, backend: &mut dyn Backend, cfgs: &Cfgs) -> Result<()> {
+        let connected: Vec<_> = self.iter().map(|o| (o.model(), o.serial())).collect();
+        trace!("connected displays: {:?}", connected);
+        let verdicts = self.rank(cfgs);
+        trace!("ranked cfgs: {:?}", verdicts);
+        if let Some(best) = verdicts.first().filter(|v| v.rank.is_some()) {
             info!(
                 "activating config '{}' (priority: {})",
-                name,
-                best_cfg.priority.unwrap_or(0)
+                best.name, best.priority
             );
-            self.set_models(&best_cfg.outputs)?;
+            self.set_models(backend, &cfgs[&best.name], false)?;
+        }
+        Ok(())
+    }
+
+    /// Check every configuration of `cfgs` against the connected outputs. The usable ones come
+    /// first, best first, followed by the rejected ones.
+    pub fn rank(&self, cfgs: &Cfgs) -> Vec<Verdict> {
+        let mut verdicts: Vec<Verdict> = cfgs
+            .iter()
+            .map(|(name, cfg)| {
+                let mut verdict = Verdict {
+                    name: name.clone(),
+                    priority: cfg.priority.unwrap_or(0),
+                    rank: None,
+                    outputs: 0,
+                    exact: 0,
+                    rejected: None,
+                };
+                match self.match_setup(&cfg.outputs) {
+                    Err(e) => verdict.rejected = Some(self.missing(cfg).unwrap_or(e.to_string())),
+                    // a config of only missing optional outputs would disable everything
+                    Ok(matched) if matched.is_empty() => {
+                        verdict.rejected = Some("None of its outputs is connected".to_string())
+                    }
+                    Ok(matched) => {
+                        verdict.outputs = matched.len();
+                        verdict.exact = matched.iter().filter(|(d, _)| d.is_exact()).count();
+                        if cfg.match_mode == Some(MatchMode::Exact) && matched.len() != self.0.len()
+                        {
+                            verdict.rejected =
+                                Some("Other outputs are connected as well".to_string());
+                        } else if let Err(e) = self.plan_models(cfg) {
+                            // e.g. an unavailable mode or overlapping positions
+                            verdict.rejected = Some(e.to_string());
+                        }
+                    }
+                }
+                verdict
+            })
+            .collect();
+
+        // usable configs first, then by priority (higher wins), number of outputs (bigger wins),
+        // number of outputs named exactly instead of by pattern (more wins) and finally the order
+        // in the file as the sort is stable (earlier wins)
+        verdicts.sort_by(|a, b| {
+            a.rejected
+                .is_some()
+                .cmp(&b.rejected.is_some())
+                .then(b.priority.cmp(&a.priority))
+                .then(b.outputs.cmp(&a.outputs))
+                .then(b.exact.cmp(&a.exact))
+        });
+        for (i, verdict) in verdicts.iter_mut().enumerate() {
+            if verdict.rejected.is_none() {
+                verdict.rank = Some(i + 1);
+            }
+        }
+        verdicts
+    }
+
+    /// Describe the required outputs of `cfg` that no connected output matches, if any.
+    fn missing(&self, cfg: &Config) -> Option<String> {
+        let missing: Vec<_> = cfg
+            .outputs
+            .iter()
+            .filter(|d| !d.optional && !d.is_catch_all())
+            .filter(|d| !self.0.iter().any(|o| d.matches(o)))
+            .map(|d| format!("'{}'", d.id()))
+            .collect();
+        (!missing.is_empty()).then(|| format!("Not connected: {}", missing.join(", ")))
+    }
+}
+
+/// Whether and how well a configuration fits the connected outputs, see [`Outputs::rank`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Verdict {
+    pub name: String,
+    pub priority: i64,
+    /// Position in the ranking starting at 1, none if rejected
+    pub rank: Option<usize>,
+    /// Number of outputs the configuration enables
+    pub outputs: usize,
+    /// Number of those outputs named exactly instead of by pattern
+    pub exact: usize,
+    /// Why the configuration can not be used
+    pub rejected: Option<String>,
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.rejected {
+            Some(reason) => write!(
+                f,
+                "-  {} (priority: {}): rejected, {}",
+                self.name, self.priority, reason
+            ),
+            None => write!(
+                f,
+                "{}. {} (priority: {}, outputs: {}, exact: {})",
+                self.rank.unwrap_or_default(),
+                self.name,
+                self.priority,
+                self.outputs,
+                self.exact
+            ),
+        }
+    }
+}
+
+/// Find an output for `entry` among its `candidates`, moving the owners of taken outputs to
+/// other candidates of theirs where possible (augmenting path of a bipartite matching).
+fn assign(
+    entry: usize,
+    candidates: &[Vec<usize>],
+    owner: &mut [Option<usize>],
+    visited: &mut [bool],
+) -> bool {
+    // keep the assignments made so far if a free output is left
+    if let Some(&o) = candidates[entry].iter().find(|o| owner[**o].is_none()) {
+        owner[o] = Some(entry);
+        return true;
+    }
+    for &o in &candidates[entry] {
+        if visited[o] {
+            continue;
+        }
+        visited[o] = true;
+        if owner[o].is_none_or(|other| assign(other, candidates, owner, visited)) {
+            owner[o] = Some(entry);
+            return true;
+        }
+    }
+    false
+}
+
+/// Serialize positions like the `position` of configs.
+fn serialize_position<S: serde::Serializer>(
+    position: &(i32, i32),
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    let (x, y) = *position;
+    Position { x, y }.serialize(serializer)
+}
+
+/// Position `outputs` according to `placements` at the same index.
+fn arrange(
+    outputs: Vec<Output>,
+    placements: &[Placement],
+    arrangement: Arrangement,
+    align: Align,
+) -> Result<Vec<Output>> {
+    let nodes: Vec<_> = outputs
+        .iter()
+        .enumerate()
+        .map(|(i, o)| Node {
+            name: o.model(),
+            size: o.logical_size(),
+            placement: placements.get(i).copied().unwrap_or(Placement::Auto),
+        })
+        .collect();
+    let positions = layout::solve(&nodes, arrangement, align)?;
+    Ok(outputs
+        .into_iter()
+        .zip(positions)
+        .map(|(o, position)| o.with_position(position))
+        .collect())
+}
+
+/// Translate the position and relative directives of `setup` into placements, resolving the
+/// referenced outputs to their index in `setup`. `setup` has one entry per matched output, i.e.
+/// catch-all entries repeated for each of their outputs and optional ones left out if missing.
+///
+/// References name an entry as written in the configuration or the model or connector of the
+/// output an entry matched. Outputs placed relative to a `missing` optional output are placed
+/// automatically instead.
+fn placements(
+    setup: &[(&DesiredOutput, &Output)],
+    missing: &[&DesiredOutput],
+) -> Result<Vec<Placement>> {
+    let anchor = |reference: &str| {
+        let by_entry: Vec<_> = (0..setup.len())
+            .filter(|i| setup[*i].0.is_referenced_by(reference))
+            .collect();
+        if by_entry.first().is_some_and(|i| setup[*i].0.is_catch_all()) {
+            return Err(eyre!(
+                "Output '{}' may stand for any number of outputs and can not be used as an anchor",
+                reference
+            ));
+        }
+        let candidates = if by_entry.is_empty() {
+            (0..setup.len())
+                .filter(|i| setup[*i].1.model() == reference || setup[*i].1.name() == reference)
+                .collect()
+        } else {
+            by_entry
+        };
+        match candidates[..] {
+            [i] => Ok(Some(i)),
+            [] if missing.iter().any(|d| d.is_referenced_by(reference)) => Ok(None),
+            [] => Err(eyre!("Output '{}' is not part of the setup", reference)),
+            _ => Err(eyre!(
+                "Output '{}' is ambiguous, add the serial to refer to it",
+                reference
+            )),
+        }
+    };
+
+    setup
+        .iter()
+        .enumerate()
+        .map(|(i, (desired, _))| {
+            let align = desired.align.unwrap_or_default();
+            let relative = [
+                (Direction::LeftOf, &desired.left_of),
+                (Direction::RightOf, &desired.right_of),
+                (Direction::Above, &desired.above),
+                (Direction::Below, &desired.below),
+            ];
+            let mut relative = relative
+                .into_iter()
+                .filter_map(|(direction, reference)| reference.as_ref().map(|r| (direction, r)));
+            match (desired.position, relative.next(), relative.next()) {
+                (None, None, _) => Ok(Placement::Auto),
+                (Some(OutputPosition::At(p)), None, _) => Ok(Placement::Fixed(p.x, p.y)),
+                (Some(OutputPosition::Side(side)), None, _) => {
+                    let direction = match side {
+                        Side::Left => Direction::LeftOf,
+                        Side::Right => Direction::RightOf,
+                        Side::Above => Direction::Above,
+                        Side::Below => Direction::Below,
+                    };
+                    let anchor = i.checked_sub(1).ok_or_else(|| {
+                        eyre!(
+                            "Output '{}' has no preceding output to be placed next to",
+                            desired.id()
+                        )
+                    })?;
+                    Ok(Placement::Relative {
+                        direction,
+                        anchor,
+                        align,
+                    })
+                }
+                (None, Some((direction, reference)), None) => Ok(match anchor(reference)? {
+                    Some(anchor) => Placement::Relative {
+                        direction,
+                        anchor,
+                        align,
+                    },
+                    None => Placement::Auto,
+                }),
+                _ => Err(eyre!(
+                    "Output '{}' can only have one of position, left-of, right-of, above and below",
+                    desired.id()
+                )),
+            }
+        })
+        .collect()
+}
+
+/// A single property of an output that changes between two states.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub property: &'static str,
+    pub current: Value,
+    pub planned: Value,
+}
+
+/// The value of a property of an output, serialized like the property itself.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    Enabled(bool),
+    #[serde(serialize_with = "serialize_position")]
+    Position((i32, i32)),
+    Mode(Option<Mode>),
+    Scale(f64),
+    Transform(Transform),
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Enabled(enabled) => write!(f, "{}", enabled),
+            Value::Position((x, y)) => write!(f, "{}/{}", x, y),
+            Value::Mode(Some(mode)) => write!(f, "{}", mode),
+            Value::Mode(None) => write!(f, "none"),
+            Value::Scale(scale) => write!(f, "{}", scale),
+            Value::Transform(transform) => write!(f, "{}", transform),
+        }
+    }
+}
+
+/// All changes of one output, see [`Outputs::diff`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutputDiff {
+    pub name: String,
+    pub model: String,
+    pub changes: Vec<Change>,
+}
+
+impl std::fmt::Display for OutputDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.model)?;
+        if self.changes.is_empty() {
+            return write!(f, ": unchanged");
+        }
+        for c in &self.changes {
+            write!(f, "\n  {}: {} -> {}", c.property, c.current, c.planned)?;
         }
         Ok(())
     }
@@ -320,18 +1040,871 @@ impl<'a> FromIterator<&'a Output> for Outputs {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::Fake;
+
+    fn mode(width: i32, height: i32) -> Mode {
+        Mode {
+            width,
+            height,
+            refresh: 60000,
+        }
+    }
+
+    fn config(outputs: Vec<DesiredOutput>) -> Config {
+        Config {
+            outputs,
+            ..Default::default()
+        }
+    }
+
+    fn fake() -> Fake {
+        Fake::new(vec![
+            Output::new("eDP-1", "LG Display 0x058B")
+                .enable()
+                .with_modes(vec![mode(1920, 1200)]),
+            Output::new("DP-1", "Dell Inc. DELL P2723DE")
+                .with_modes(vec![mode(1920, 1080), mode(2560, 1440)]),
+        ])
+    }
+
+    fn cfgs(s: &str) -> Cfgs {
+        let doc: toml_edit::Document = s.parse().unwrap();
+        Cfgs::try_from(doc.as_table()).unwrap()
+    }
 
     #[test]
-    fn padding() {
-        let output = Output::new(
-            "1234".to_owned(),
-            "model".to_owned(),
-            (0, 0),
-            (0, 0),
-            1.0,
-            true,
-            Vec::new(),
+    fn set_models_places_scaled_outputs() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let cfg = config(vec![
+            DesiredOutput {
+                name: "LG Display 0x058B".parse().unwrap(),
+                scale: Some(1.5),
+                ..Default::default()
+            },
+            DesiredOutput {
+                name: "Dell Inc. DELL P2723DE".parse().unwrap(),
+                ..Default::default()
+            },
+        ]);
+        outputs.set_models(&mut backend, &cfg, false).unwrap();
+        assert_eq!(
+            backend.commands(),
+            [
+                "output eDP-1 enable position 0 0 resolution 1920x1200 scale 1.5 transform normal",
+                "output DP-1 enable position 1280 0 resolution 2560x1440 scale 1 transform normal",
+            ]
+        );
+    }
+
+    #[test]
+    fn set_models_rotated() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let cfg = config(vec![
+            DesiredOutput {
+                name: "Dell Inc. DELL P2723DE".parse().unwrap(),
+                transform: Some(Transform::Rotate270),
+                scale: Some(2.0),
+                ..Default::default()
+            },
+            DesiredOutput {
+                name: "LG Display 0x058B".parse().unwrap(),
+                ..Default::default()
+            },
+        ]);
+        outputs.set_models(&mut backend, &cfg, false).unwrap();
+        assert_eq!(
+            backend.commands(),
+            [
+                "output DP-1 enable position 0 0 resolution 2560x1440 scale 2 transform 270",
+                "output eDP-1 enable position 720 0 resolution 1920x1200 scale 1 transform normal",
+            ]
+        );
+    }
+
+    #[test]
+    fn set_models_explicit_position() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let laptop = |y| DesiredOutput {
+            name: "LG Display 0x058B".parse().unwrap(),
+            position: Some(OutputPosition::At(Position { x: 320, y })),
+            ..Default::default()
+        };
+        let dell = DesiredOutput {
+            name: "Dell Inc. DELL P2723DE".parse().unwrap(),
+            ..Default::default()
+        };
+        let cfg = config(vec![laptop(1440), dell.clone()]);
+        outputs.set_models(&mut backend, &cfg, false).unwrap();
+        assert_eq!(
+            backend.commands(),
+            [
+                "output eDP-1 enable position 320 1440 resolution 1920x1200 scale 1 transform normal",
+                "output DP-1 enable position 0 0 resolution 2560x1440 scale 1 transform normal",
+            ]
+        );
+
+        let mut backend = fake();
+        let cfg = config(vec![laptop(1000), dell]);
+        let err = outputs.set_models(&mut backend, &cfg, false).unwrap_err();
+        assert!(err.to_string().contains("overlap"), "{}", err);
+        assert!(backend.commands().is_empty());
+    }
+
+    #[test]
+    fn set_models_relative() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let cfgs = cfgs(
+            r#"
+            [desk]
+            outputs = [
+                { name = "LG Display 0x058B", below = "Dell Inc. DELL P2723DE", align = "center" },
+                { name = "Dell Inc. DELL P2723DE" },
+            ]
+            [invalid]
+            outputs = [
+                { name = "LG Display 0x058B", below = "Dell Inc. DELL P2723DE", left-of = "Foo" },
+            ]
+            "#,
+        );
+        outputs
+            .set_models(&mut backend, &cfgs["desk"], false)
+            .unwrap();
+        assert_eq!(
+            backend.commands(),
+            [
+                "output eDP-1 enable position 320 1440 resolution 1920x1200 scale 1 transform normal",
+                "output DP-1 enable position 0 0 resolution 2560x1440 scale 1 transform normal",
+            ]
+        );
+        assert!(outputs
+            .set_models(&mut backend, &cfgs["invalid"], false)
+            .is_err());
+    }
+
+    #[test]
+    fn set_models_by_connector() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let cfgs = cfgs(
+            r#"
+            [meeting]
+            outputs = [
+                { connector = "eDP-1", right-of = "DP-1" },
+                { name = "Dell *", connector = "DP-1" },
+            ]
+            "#,
+        );
+        outputs
+            .set_models(&mut backend, &cfgs["meeting"], false)
+            .unwrap();
+        assert_eq!(
+            backend.commands(),
+            [
+                "output eDP-1 enable position 2560 0 resolution 1920x1200 scale 1 transform normal",
+                "output DP-1 enable position 0 0 resolution 2560x1440 scale 1 transform normal",
+            ]
+        );
+    }
+
+    #[test]
+    fn set_models_relative_to_matched_output() {
+        let desks = cfgs(
+            r#"
+            [model]
+            outputs = ["Dell *", { name = "LG Display 0x058B", below = "Dell Inc. DELL P2723DE" }]
+            [connector]
+            outputs = ["Dell *", { name = "LG Display 0x058B", below = "DP-1" }]
+            [unknown]
+            outputs = ["Dell *", { name = "LG Display 0x058B", below = "Dell Inc. DELL P2421" }]
+            "#,
+        );
+        for desk in ["model", "connector"] {
+            let mut backend = fake();
+            let outputs = Outputs::query(&mut backend).unwrap();
+            outputs
+                .set_models(&mut backend, &desks[desk], false)
+                .unwrap();
+            assert_eq!(
+                backend.commands(),
+                [
+                    "output DP-1 enable position 0 0 resolution 2560x1440 scale 1 transform normal",
+                    "output eDP-1 enable position 0 1440 resolution 1920x1200 scale 1 transform normal",
+                ],
+                "{}",
+                desk
+            );
+        }
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let err = outputs
+            .set_models(&mut backend, &desks["unknown"], false)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Output 'Dell Inc. DELL P2421' is not part of the setup"
+        );
+    }
+
+    #[test]
+    fn set_models_catch_all() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let setups = cfgs(
+            r#"
+            [anything]
+            outputs = ["LG Display 0x058B", { name = "*", scale = 2.0, position = "right" }]
+            [alone]
+            outputs = ["LG Display 0x058B"]
+            "#,
+        );
+        let matched = outputs.match_setup(&setups["anything"].outputs).unwrap();
+        assert_eq!(matched.len(), 2);
+        assert!(matched[1].0.is_catch_all());
+        assert_eq!(matched[1].1.name(), "DP-1");
+
+        // the catch-all config enables more outputs
+        outputs.activate_config(&mut backend, &setups).unwrap();
+        assert_eq!(
+            backend.commands(),
+            [
+                "output eDP-1 enable position 0 0 resolution 1920x1200 scale 1 transform normal",
+                "output DP-1 enable position 1920 0 resolution 2560x1440 scale 2 transform normal",
+            ]
+        );
+
+        let first = cfgs(r#"first.outputs = [{ name = "*", position = "right" }]"#);
+        let err = outputs
+            .set_models(&mut backend, &first["first"], false)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Output '*' has no preceding output to be placed next to"
+        );
+
+        // every further output of the catch-all is placed next to the previous one
+        let mut backend = Fake::new(vec![
+            Output::new("eDP-1", "LG Display 0x058B").with_modes(vec![mode(1920, 1200)]),
+            Output::new("DP-1", "Dell Inc. DELL P2723DE").with_modes(vec![mode(2560, 1440)]),
+            Output::new("DP-2", "Dell Inc. DELL P2421").with_modes(vec![mode(1920, 1080)]),
+        ]);
+        let outputs = Outputs::query(&mut backend).unwrap();
+        outputs
+            .set_models(&mut backend, &setups["anything"], false)
+            .unwrap();
+        assert_eq!(
+            backend.commands(),
+            [
+                "output eDP-1 enable position 0 0 resolution 1920x1200 scale 1 transform normal",
+                "output DP-2 enable position 1920 0 resolution 1920x1080 scale 2 transform normal",
+                "output DP-1 enable position 2880 0 resolution 2560x1440 scale 2 transform normal",
+            ]
+        );
+
+        // the catch-all is no anchor, even if it takes a single output
+        let cfg = config(vec![
+            DesiredOutput {
+                name: "LG Display 0x058B".parse().unwrap(),
+                below: Some("*".to_string()),
+                ..Default::default()
+            },
+            DesiredOutput {
+                name: "*".parse().unwrap(),
+                ..Default::default()
+            },
+        ]);
+        let err = outputs.set_models(&mut backend, &cfg, false).unwrap_err();
+        assert!(
+            err.to_string().contains("can not be used as an anchor"),
+            "{}",
+            err
+        );
+
+        // without other outputs, the catch-all takes none
+        let mut backend = Fake::new(vec![Output::new("eDP-1", "LG Display 0x058B")]);
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let matched = outputs.match_setup(&setups["anything"].outputs).unwrap();
+        assert_eq!(matched.len(), 1);
+    }
+
+    #[test]
+    fn set_models_optional() {
+        let setups = cfgs(
+            r#"
+            [office]
+            outputs = [
+                { name = "Dell Inc. DELL P2723DE", optional = true },
+                { name = "LG Display 0x058B", below = "Dell Inc. DELL P2723DE" },
+            ]
+            [projector]
+            outputs = [{ connector = "HDMI-A-1", optional = true }]
+            priority = 5
+            [alone]
+            outputs = ["LG Display 0x058B"]
+            "#,
+        );
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        // more present outputs win
+        outputs.activate_config(&mut backend, &setups).unwrap();
+        assert_eq!(
+            backend.commands(),
+            [
+                "output DP-1 enable position 0 0 resolution 2560x1440 scale 1 transform normal",
+                "output eDP-1 enable position 0 1440 resolution 1920x1200 scale 1 transform normal",
+            ]
+        );
+
+        let mut backend = Fake::new(vec![
+            Output::new("eDP-1", "LG Display 0x058B").with_modes(vec![mode(1920, 1200)])
+        ]);
+        let outputs = Outputs::query(&mut backend).unwrap();
+        assert!(outputs
+            .match_setup(&setups["projector"].outputs)
+            .unwrap()
+            .is_empty());
+        outputs
+            .set_models(&mut backend, &setups["office"], false)
+            .unwrap();
+        assert_eq!(
+            backend.commands(),
+            ["output eDP-1 enable position 0 0 resolution 1920x1200 scale 1 transform normal"]
+        );
+    }
+
+    #[test]
+    fn activate_config_exact_match() {
+        let setups = cfgs(
+            r#"
+            [alone]
+            outputs = ["LG Display 0x058B"]
+            priority = 1
+            match = "exact"
+            [home]
+            outputs = ["LG Display 0x058B", "Dell Inc. DELL P2723DE"]
+            "#,
+        );
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        outputs.activate_config(&mut backend, &setups).unwrap();
+        assert_eq!(
+            backend.commands()[0],
+            "output eDP-1 enable position 0 0 resolution 1920x1200 scale 1 transform normal"
+        );
+        assert!(backend.commands()[1].starts_with("output DP-1 enable"));
+
+        let mut backend = Fake::new(vec![
+            Output::new("eDP-1", "LG Display 0x058B").with_modes(vec![mode(1920, 1200)])
+        ]);
+        let outputs = Outputs::query(&mut backend).unwrap();
+        outputs.activate_config(&mut backend, &setups).unwrap();
+        assert_eq!(
+            backend.commands(),
+            ["output eDP-1 enable position 0 0 resolution 1920x1200 scale 1 transform normal"]
+        );
+    }
+
+    #[test]
+    fn rank_configs() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let setups = cfgs(
+            r#"
+            [alone]
+            outputs = ["LG Display 0x058B"]
+            match = "exact"
+            [home]
+            outputs = ["LG Display 0x058B", "Dell Inc. DELL P2723DE"]
+            [hotdesk]
+            outputs = ["LG Display 0x058B", "Dell Inc. *"]
+            [office]
+            outputs = ["Dell Inc. DELL P2421", "Dell Inc. DELL U2713HM"]
+            priority = 2
+            [projector]
+            outputs = [{ connector = "HDMI-A-1", optional = true }]
+            "#,
+        );
+        let ranking: Vec<_> = outputs
+            .rank(&setups)
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            ranking[..2],
+            [
+                "1. home (priority: 0, outputs: 2, exact: 2)",
+                "2. hotdesk (priority: 0, outputs: 2, exact: 1)",
+            ]
+        );
+        assert_eq!(ranking[2], "-  office (priority: 2): rejected, Not connected: 'Dell Inc. DELL P2421', 'Dell Inc. DELL U2713HM'");
+        let mut rejected = ranking[3..].to_vec();
+        rejected.sort();
+        assert_eq!(
+            rejected,
+            [
+                "-  alone (priority: 0): rejected, Other outputs are connected as well",
+                "-  projector (priority: 0): rejected, None of its outputs is connected",
+            ]
+        );
+    }
+
+    #[test]
+    fn rank_rejects_unplannable_configs() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let setups = cfgs(
+            r#"
+            [home]
+            outputs = ["LG Display 0x058B", { name = "Dell Inc. DELL P2723DE", mode = "3840x2160" }]
+            [alone]
+            outputs = ["LG Display 0x058B"]
+            "#,
+        );
+        let ranking = outputs.rank(&setups);
+        assert_eq!(ranking[0].name, "alone");
+        assert_eq!(ranking[0].rank, Some(1));
+        assert_eq!(ranking[1].name, "home");
+        assert!(ranking[1]
+            .rejected
+            .as_ref()
+            .unwrap()
+            .starts_with("Mode 3840x2160 is not available for 'Dell Inc. DELL P2723DE' (DP-1)"));
+
+        // alone is already applied, home must not be tried
+        outputs.activate_config(&mut backend, &setups).unwrap();
+        assert_eq!(backend.commands().len(), 1);
+    }
+
+    #[test]
+    fn rank_rejects_oversized_layouts() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let setups = cfgs(
+            r#"
+            [tiny]
+            outputs = [{ name = "LG Display 0x058B", scale = 1e-9 }, "Dell Inc. DELL P2723DE"]
+            "#,
         );
+        let ranking = outputs.rank(&setups);
+        assert!(ranking[0]
+            .rejected
+            .as_ref()
+            .unwrap()
+            .starts_with("Outputs exceed the coordinates of the layout"));
+    }
+
+    #[test]
+    fn rank_ties_by_file_order() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let setups = cfgs(
+            r#"
+            b.outputs = ["LG Display 0x058B"]
+            a.outputs = ["LG Display 0x058B"]
+            home.outputs = ["LG Display 0x058B", "Dell Inc. DELL P2723DE"]
+            d.outputs = ["LG Display 0x058B"]
+            c.outputs = ["LG Display 0x058B"]
+            "#,
+        );
+        let ranking: Vec<_> = outputs.rank(&setups).into_iter().map(|v| v.name).collect();
+        // home has more outputs, the tied rest keeps the order of the file
+        assert_eq!(ranking, ["home", "b", "a", "d", "c"]);
+    }
+
+    #[test]
+    fn parse_transform() {
+        assert_eq!(
+            "flipped-90".parse::<Transform>().unwrap(),
+            Transform::Flipped90
+        );
+        assert_eq!(Transform::Flipped90.to_string(), "flipped-90");
+        assert!("45".parse::<Transform>().is_err());
+    }
+
+    #[test]
+    fn set_models_rolls_back() {
+        let mut backend = fake().fail_on("output DP-1 enable");
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let cfg = config(vec![
+            DesiredOutput {
+                name: "LG Display 0x058B".parse().unwrap(),
+                scale: Some(2.0),
+                ..Default::default()
+            },
+            DesiredOutput {
+                name: "Dell Inc. DELL P2723DE".parse().unwrap(),
+                ..Default::default()
+            },
+        ]);
+        assert!(outputs.set_models(&mut backend, &cfg, false).is_err());
+        assert_eq!(
+            &backend.commands()[2..],
+            [
+                "output eDP-1 enable position 0 0 scale 1 transform normal",
+                "output DP-1 disable",
+            ]
+        );
+    }
+
+    #[test]
+    fn set_models_disconnected() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let cfg = config(vec![DesiredOutput {
+            name: "Foo".parse().unwrap(),
+            ..Default::default()
+        }]);
+        assert!(outputs.set_models(&mut backend, &cfg, false).is_err());
+        assert!(backend.commands().is_empty());
+    }
+
+    #[test]
+    fn set_by_name_disables_others() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        outputs
+            .set_by_name(&mut backend, &["DP-1".to_string()], false)
+            .unwrap();
+        assert_eq!(
+            backend.commands(),
+            [
+                "output DP-1 enable position 0 0 resolution 2560x1440 scale 1 transform normal",
+                "output eDP-1 disable",
+            ]
+        );
+    }
+
+    #[test]
+    fn set_by_name_unknown() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let setup = ["DP-1".to_string(), "DP-7".to_string()];
+        let err = outputs
+            .set_by_name(&mut backend, &setup, false)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Display 'DP-7' is not connected");
+        assert!(backend.commands().is_empty());
+    }
+
+    #[test]
+    fn refuse_disabling_every_output() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        assert!(outputs.set_by_name(&mut backend, &[], false).is_err());
+        assert!(outputs
+            .set_models(&mut backend, &config(vec![]), false)
+            .is_err());
+        assert!(backend.commands().is_empty());
+
+        outputs.set_by_name(&mut backend, &[], true).unwrap();
+        assert_eq!(backend.commands(), ["output eDP-1 disable"]);
+    }
+
+    #[test]
+    fn diff_config() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let cfg = config(vec![
+            DesiredOutput {
+                name: "Dell Inc. DELL P2723DE".parse().unwrap(),
+                ..Default::default()
+            },
+            DesiredOutput {
+                name: "LG Display 0x058B".parse().unwrap(),
+                scale: Some(1.5),
+                ..Default::default()
+            },
+        ]);
+        let diff: Vec<_> = outputs
+            .diff(&cfg)
+            .unwrap()
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            diff,
+            [
+                "DP-1 (Dell Inc. DELL P2723DE)\n  enabled: false -> true\n  mode: none -> 2560x1440",
+                "eDP-1 (LG Display 0x058B)\n  position: 0/0 -> 2560/0\n  mode: none -> 1920x1200\n  scale: 1 -> 1.5",
+            ]
+        );
+        let json = serde_json::to_value(outputs.diff(&cfg).unwrap()).unwrap();
+        let changes = |i: usize| json[i]["changes"].clone();
+        assert_eq!(
+            changes(0)[0],
+            serde_json::json!({ "property": "enabled", "current": false, "planned": true })
+        );
+        assert_eq!(
+            changes(1),
+            serde_json::json!([
+                {
+                    "property": "position",
+                    "current": { "x": 0, "y": 0 },
+                    "planned": { "x": 2560, "y": 0 },
+                },
+                {
+                    "property": "mode",
+                    "current": null,
+                    "planned": { "width": 1920, "height": 1200, "refresh": 0 },
+                },
+                { "property": "scale", "current": 1.0, "planned": 1.5 },
+            ])
+        );
+        assert!(backend.commands().is_empty());
+    }
+
+    #[test]
+    fn skip_applied_outputs() {
+        let mut backend = Fake::new(vec![
+            Output::new("eDP-1", "LG Display 0x058B")
+                .enable()
+                .with_mode(Some(Mode {
+                    refresh: 60000,
+                    ..mode(1920, 1200)
+                }))
+                .with_modes(vec![mode(1920, 1200)]),
+            Output::new("DP-1", "Dell Inc. DELL P2723DE")
+                .with_modes(vec![mode(1920, 1080), mode(2560, 1440)]),
+        ]);
+        let outputs = Outputs::query(&mut backend).unwrap();
+        outputs
+            .set_by_name(&mut backend, &["eDP-1".to_string()], false)
+            .unwrap();
+        assert!(backend.commands().is_empty());
+
+        // only the changed output is touched
+        outputs
+            .set_by_name(
+                &mut backend,
+                &["eDP-1".to_string(), "DP-1".to_string()],
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            backend.commands(),
+            ["output DP-1 enable position 1920 0 resolution 2560x1440 scale 1 transform normal"]
+        );
+    }
+
+    fn twins() -> Fake {
+        let dell = |name: &str| {
+            Output::new(name, "Dell Inc. DELL P2421").with_modes(vec![mode(1920, 1080)])
+        };
+        Fake::new(vec![
+            dell("DP-2").with_serial("B"),
+            dell("DP-1").with_serial("A"),
+            dell("DP-3"),
+        ])
+    }
+
+    #[test]
+    fn identical_models_are_kept() {
+        let mut backend = twins();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        assert_eq!(outputs.len(), 3);
+        let names: Vec<_> = outputs.iter().map(|o| o.name()).collect();
+        assert_eq!(names, ["DP-3", "DP-1", "DP-2"]);
+    }
+
+    #[test]
+    fn match_setup_by_serial() {
+        let mut backend = twins();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let dell = |serial: Option<&str>| DesiredOutput {
+            name: "Dell Inc. DELL P2421".parse().unwrap(),
+            serial: serial.map(str::to_string),
+            ..Default::default()
+        };
+        let names: Vec<_> = outputs
+            .match_setup(&[dell(None), dell(Some("B")), dell(None)])
+            .unwrap()
+            .iter()
+            .map(|(_, o)| o.name())
+            .collect();
+        assert_eq!(names, ["DP-3", "DP-2", "DP-1"]);
+        assert!(outputs.match_setup(&[dell(Some("C"))]).is_err());
+        assert!(outputs.match_setup(&vec![dell(None); 4]).is_err());
+    }
+
+    #[test]
+    fn match_setup_by_pattern() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let desired = |name: &str, connector: Option<&str>| DesiredOutput {
+            name: name.parse().unwrap(),
+            connector: connector.map(|c| c.parse().unwrap()),
+            ..Default::default()
+        };
+        // the exact entry is matched first although the glob comes first
+        let names: Vec<_> = outputs
+            .match_setup(&[
+                desired("regex:.*", None),
+                desired("Dell Inc. DELL P2723DE", None),
+            ])
+            .unwrap()
+            .iter()
+            .map(|(_, o)| o.name())
+            .collect();
+        assert_eq!(names, ["eDP-1", "DP-1"]);
+
+        // overlapping patterns: the first one gives up the Dell the second one can only take
+        let names: Vec<_> = outputs
+            .match_setup(&[desired("regex:.*", None), desired("Dell Inc. *", None)])
+            .unwrap()
+            .iter()
+            .map(|(_, o)| o.name())
+            .collect();
+        assert_eq!(names, ["eDP-1", "DP-1"]);
+        let names: Vec<_> = outputs
+            .match_setup(&[desired("Dell Inc. *", None), desired("regex:.*", None)])
+            .unwrap()
+            .iter()
+            .map(|(_, o)| o.name())
+            .collect();
+        assert_eq!(names, ["DP-1", "eDP-1"]);
+
+        let setup = [desired("regex:(LG|Dell) .*", Some("DP-*"))];
+        assert_eq!(outputs.match_setup(&setup).unwrap()[0].1.name(), "DP-1");
+        let setup = [desired("", Some("eDP-1"))];
+        assert_eq!(outputs.match_setup(&setup).unwrap()[0].1.name(), "eDP-1");
+        assert!(outputs
+            .match_setup(&[desired("LG *", Some("DP-?"))])
+            .is_err());
+    }
+
+    #[test]
+    fn activate_config_prefers_exact() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let desks = cfgs(
+            r#"
+            [hotdesk]
+            outputs = ["LG Display 0x058B", "Dell Inc. *"]
+            [home]
+            outputs = ["LG Display 0x058B", "Dell Inc. DELL P2723DE"]
+            [alone]
+            outputs = ["LG Display 0x058B"]
+            "#,
+        );
+        outputs.activate_config(&mut backend, &desks).unwrap();
+        assert_eq!(
+            backend.commands()[1],
+            "output DP-1 enable position 1920 0 resolution 2560x1440 scale 1 transform normal"
+        );
+
+        let mut backend = fake();
+        let scaled = cfgs(
+            r#"
+            [hotdesk]
+            outputs = ["LG Display 0x058B", { name = "Dell Inc. *", scale = 2.0 }]
+            [alone]
+            outputs = ["LG Display 0x058B"]
+            "#,
+        );
+        outputs.activate_config(&mut backend, &scaled).unwrap();
+        assert_eq!(
+            backend.commands()[1],
+            "output DP-1 enable position 1920 0 resolution 2560x1440 scale 2 transform normal"
+        );
+    }
+
+    #[test]
+    fn select_mode() {
+        let output = Output::new("DP-1", "Dell").with_modes(vec![
+            Mode {
+                refresh: 143998,
+                ..mode(2560, 1440)
+            },
+            mode(2560, 1440),
+            mode(3840, 2160),
+        ]);
+        let select = |m: &str| output.select_mode(&m.parse().unwrap());
+        assert_eq!(
+            select("2560x1440@144").unwrap().to_string(),
+            "2560x1440@143.998Hz"
+        );
+        assert_eq!(
+            select("2560x1440@60Hz").unwrap().to_string(),
+            "2560x1440@60.000Hz"
+        );
+        assert_eq!(select("3840x2160").unwrap().to_string(), "3840x2160");
+        let err = select("3840x2160@120").unwrap_err().to_string();
+        assert!(
+            err.contains("available modes: 2560x1440@143.998Hz"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn best_mode_by_policy() {
+        let output = Output::new("DP-1", "Dell");
+        assert_eq!(output.best_mode(ModePolicy::Largest), None);
+
+        let output = output.with_modes(vec![
+            Mode {
+                refresh: 143998,
+                ..mode(2560, 1440)
+            },
+            Mode {
+                refresh: 30000,
+                ..mode(3840, 2160)
+            },
+            mode(3840, 2160),
+        ]);
+        let best = |policy| output.best_mode(policy).unwrap().to_string();
+        assert_eq!(best(ModePolicy::Largest), "3840x2160");
+        assert_eq!(best(ModePolicy::HighestRefresh), "2560x1440@143.998Hz");
+        assert_eq!(best(ModePolicy::LargestThenRefresh), "3840x2160@60.000Hz");
+    }
+
+    #[test]
+    fn set_models_with_mode() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let cfg = config(vec![DesiredOutput {
+            name: "Dell Inc. DELL P2723DE".parse().unwrap(),
+            width: Some(1920),
+            height: Some(1080),
+            refresh: Some(60.0),
+            ..Default::default()
+        }]);
+        outputs.set_models(&mut backend, &cfg, false).unwrap();
+        assert_eq!(
+            backend.commands()[0],
+            "output DP-1 enable position 0 0 resolution 1920x1080@60.000Hz scale 1 transform normal"
+        );
+    }
+
+    #[test]
+    fn activate_config_by_priority() {
+        let mut backend = fake();
+        let outputs = Outputs::query(&mut backend).unwrap();
+        let cfgs = cfgs(
+            r#"
+            [alone]
+            outputs = [{ name = "LG Display 0x058B" }]
+            priority = 1
+            [home]
+            outputs = [{ name = "LG Display 0x058B" }, { name = "Dell Inc. DELL P2723DE" }]
+            [office]
+            outputs = [{ name = "Dell Inc. DELL P2421" }]
+            priority = 2
+            "#,
+        );
+        outputs.activate_config(&mut backend, &cfgs).unwrap();
+        assert_eq!(
+            backend.commands(),
+            ["output eDP-1 enable position 0 0 resolution 1920x1200 scale 1 transform normal"]
+        );
+    }
+
+    #[test]
+    fn padding() {
+        let output = Output::new("1234", "model").enable();
         let display = output.display(false, 8);
         assert_eq!(&display[..10], "1234:     ");
     }
@@ -0,0 +1,355 @@
+//! Layout solver turning placement constraints into output positions.
+
+use color_eyre::{eyre::eyre, Result};
+
+use crate::cfg::Align;
+
+/// Where an output goes in the layout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Placement {
+    /// Next in the automatic arrangement, left to right
+    Auto,
+    /// Absolute position
+    Fixed(i32, i32),
+    /// Next to the output with index `anchor`
+    Relative {
+        direction: Direction,
+        anchor: usize,
+        align: Align,
+    },
+}
+
+/// How outputs with [`Placement::Auto`] are arranged, in the order they are given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arrangement {
+    /// Left to right
+    #[default]
+    Horizontal,
+    /// Top to bottom
+    Vertical,
+    /// Rows of `columns` outputs, filled left to right and top to bottom
+    Grid { columns: usize },
+}
+
+/// Side of the anchor an output is placed at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    LeftOf,
+    RightOf,
+    Above,
+    Below,
+}
+
+/// An output to place: its name for messages, its logical size and its placement.
+#[derive(Debug, Clone)]
+pub struct Node<'a> {
+    pub name: &'a str,
+    pub size: (i32, i32),
+    pub placement: Placement,
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Unresolved,
+    InProgress,
+    Done((i32, i32)),
+}
+
+/// Compute the positions of all `nodes`, arranging automatically placed ones by `arrangement`
+/// and aligning them by `align` within their row and column.
+///
+/// Unless an output has a fixed position, the layout is moved so that its top left corner is at
+/// 0/0. Fails on cyclic constraints and overlapping outputs.
+pub fn solve(nodes: &[Node], arrangement: Arrangement, align: Align) -> Result<Vec<(i32, i32)>> {
+    let mut states = vec![State::Unresolved; nodes.len()];
+
+    let auto: Vec<_> = (0..nodes.len())
+        .filter(|i| nodes[*i].placement == Placement::Auto)
+        .collect();
+    let sizes: Vec<_> = auto.iter().map(|i| nodes[*i].size).collect();
+    for (i, position) in auto.iter().zip(arrange(&sizes, arrangement, align)?) {
+        states[*i] = State::Done(position);
+    }
+    for (i, node) in nodes.iter().enumerate() {
+        if let Placement::Fixed(x, y) = node.placement {
+            states[i] = State::Done((x, y));
+        }
+    }
+
+    let mut positions = Vec::with_capacity(nodes.len());
+    for i in 0..nodes.len() {
+        positions.push(resolve(nodes, &mut states, i)?);
+    }
+
+    let fixed = nodes
+        .iter()
+        .any(|n| matches!(n.placement, Placement::Fixed(..)));
+    if !fixed {
+        let min_x = positions.iter().map(|p| p.0).min().unwrap_or(0);
+        let min_y = positions.iter().map(|p| p.1).min().unwrap_or(0);
+        for p in positions.iter_mut() {
+            *p = (sub(p.0, min_x)?, sub(p.1, min_y)?);
+        }
+    }
+
+    check_overlaps(nodes, &positions)?;
+    Ok(positions)
+}
+
+/// Positions of outputs with the given `sizes` in a grid. Every column is as wide as its widest
+/// output and every row as high as its highest one, smaller outputs are aligned within them.
+fn arrange(
+    sizes: &[(i32, i32)],
+    arrangement: Arrangement,
+    align: Align,
+) -> Result<Vec<(i32, i32)>> {
+    let columns = match arrangement {
+        Arrangement::Horizontal => sizes.len(),
+        Arrangement::Vertical => 1,
+        Arrangement::Grid { columns } => columns,
+    }
+    .max(1);
+
+    let mut widths = vec![0; columns];
+    let mut heights = vec![0; sizes.len().div_ceil(columns)];
+    for (i, (w, h)) in sizes.iter().enumerate() {
+        widths[i % columns] = widths[i % columns].max(*w);
+        heights[i / columns] = heights[i / columns].max(*h);
+    }
+
+    sizes
+        .iter()
+        .enumerate()
+        .map(|(i, (w, h))| {
+            let (column, row) = (i % columns, i / columns);
+            let x = add(sum(&widths[..column])?, offset(align, widths[column], *w)?)?;
+            let y = add(sum(&heights[..row])?, offset(align, heights[row], *h)?)?;
+            Ok((x, y))
+        })
+        .collect()
+}
+
+fn resolve(nodes: &[Node], states: &mut [State], i: usize) -> Result<(i32, i32)> {
+    match states[i] {
+        State::Done(position) => return Ok(position),
+        State::InProgress => {
+            return Err(eyre!(
+                "Cyclic placement of outputs involving '{}'",
+                nodes[i].name
+            ))
+        }
+        State::Unresolved => states[i] = State::InProgress,
+    }
+
+    let Placement::Relative {
+        direction,
+        anchor,
+        align,
+    } = nodes[i].placement
+    else {
+        unreachable!("only relative placements are unresolved");
+    };
+    let (ax, ay) = resolve(nodes, states, anchor)?;
+    let (aw, ah) = nodes[anchor].size;
+    let (w, h) = nodes[i].size;
+    let position = match direction {
+        Direction::LeftOf => (sub(ax, w)?, add(ay, offset(align, ah, h)?)?),
+        Direction::RightOf => (add(ax, aw)?, add(ay, offset(align, ah, h)?)?),
+        Direction::Above => (add(ax, offset(align, aw, w)?)?, sub(ay, h)?),
+        Direction::Below => (add(ax, offset(align, aw, w)?)?, add(ay, ah)?),
+    };
+    states[i] = State::Done(position);
+    Ok(position)
+}
+
+/// Offset along the edge of the anchor with length `anchor` for an output with length `len`.
+pub fn offset(align: Align, anchor: i32, len: i32) -> Result<i32> {
+    match align {
+        Align::Top | Align::Left => Ok(0),
+        Align::Center => Ok(sub(anchor, len)? / 2),
+        Align::Bottom | Align::Right => sub(anchor, len),
+    }
+}
+
+// Sizes come from modes and scales of configs, e.g. a tiny scale makes an output huge, so the
+// layout fails instead of overflowing.
+fn add(a: i32, b: i32) -> Result<i32> {
+    a.checked_add(b).ok_or_else(out_of_range)
+}
+
+fn sub(a: i32, b: i32) -> Result<i32> {
+    a.checked_sub(b).ok_or_else(out_of_range)
+}
+
+fn sum(lengths: &[i32]) -> Result<i32> {
+    lengths.iter().try_fold(0, |acc, len| add(acc, *len))
+}
+
+fn out_of_range() -> color_eyre::Report {
+    eyre!("Outputs exceed the coordinates of the layout, check their modes and scales")
+}
+
+fn check_overlaps(nodes: &[Node], positions: &[(i32, i32)]) -> Result<()> {
+    let rects: Vec<_> = nodes.iter().zip(positions).collect();
+    for (i, (a, (x1, y1))) in rects.iter().enumerate() {
+        for (b, (x2, y2)) in &rects[i + 1..] {
+            let ((w1, h1), (w2, h2)) = (a.size, b.size);
+            let (right1, bottom1) = (add(*x1, w1)?, add(*y1, h1)?);
+            let (right2, bottom2) = (add(*x2, w2)?, add(*y2, h2)?);
+            if *x1 < right2 && *x2 < right1 && *y1 < bottom2 && *y2 < bottom1 {
+                return Err(eyre!(
+                    "Outputs '{}' at {}/{} and '{}' at {}/{} overlap",
+                    a.name,
+                    x1,
+                    y1,
+                    b.name,
+                    x2,
+                    y2
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, size: (i32, i32), placement: Placement) -> Node<'_> {
+        Node {
+            name,
+            size,
+            placement,
+        }
+    }
+
+    fn relative(direction: Direction, anchor: usize, align: Align) -> Placement {
+        Placement::Relative {
+            direction,
+            anchor,
+            align,
+        }
+    }
+
+    #[test]
+    fn below_centered() {
+        let nodes = [
+            node(
+                "laptop",
+                (1280, 800),
+                relative(Direction::Below, 1, Align::Center),
+            ),
+            node("dell", (2560, 1440), Placement::Auto),
+        ];
+        assert_eq!(
+            solve(&nodes, Arrangement::Horizontal, Align::Top).unwrap(),
+            [(640, 1440), (0, 0)]
+        );
+    }
+
+    #[test]
+    fn left_of_is_normalized() {
+        let nodes = [
+            node("dell", (2560, 1440), Placement::Auto),
+            node(
+                "laptop",
+                (1280, 800),
+                relative(Direction::LeftOf, 0, Align::Bottom),
+            ),
+            node(
+                "tv",
+                (1280, 720),
+                relative(Direction::Above, 1, Align::Left),
+            ),
+        ];
+        assert_eq!(
+            solve(&nodes, Arrangement::Horizontal, Align::Top).unwrap(),
+            [(1280, 80), (0, 720), (0, 0)]
+        );
+    }
+
+    #[test]
+    fn arrangements() {
+        let nodes = [
+            node("a", (1920, 1080), Placement::Auto),
+            node("b", (2560, 1440), Placement::Auto),
+            node("c", (1280, 800), Placement::Auto),
+        ];
+        assert_eq!(
+            solve(&nodes, Arrangement::Horizontal, Align::Top).unwrap(),
+            [(0, 0), (1920, 0), (4480, 0)]
+        );
+        assert_eq!(
+            solve(&nodes, Arrangement::Vertical, Align::Top).unwrap(),
+            [(0, 0), (0, 1080), (0, 2520)]
+        );
+        assert_eq!(
+            solve(&nodes, Arrangement::Grid { columns: 2 }, Align::Top).unwrap(),
+            [(0, 0), (1920, 0), (0, 1440)]
+        );
+    }
+
+    #[test]
+    fn aligned_arrangements() {
+        let nodes = [
+            node("a", (1920, 1080), Placement::Auto),
+            node("b", (2560, 1440), Placement::Auto),
+        ];
+        let solve = |arrangement, align| solve(&nodes, arrangement, align).unwrap();
+        assert_eq!(
+            solve(Arrangement::Horizontal, Align::Center),
+            [(0, 180), (1920, 0)]
+        );
+        assert_eq!(
+            solve(Arrangement::Horizontal, Align::Bottom),
+            [(0, 360), (1920, 0)]
+        );
+        assert_eq!(
+            solve(Arrangement::Vertical, Align::Center),
+            [(320, 0), (0, 1080)]
+        );
+        assert_eq!(
+            solve(Arrangement::Vertical, Align::Right),
+            [(640, 0), (0, 1080)]
+        );
+    }
+
+    #[test]
+    fn cycle() {
+        let nodes = [
+            node("a", (100, 100), relative(Direction::LeftOf, 1, Align::Top)),
+            node("b", (100, 100), relative(Direction::Above, 0, Align::Left)),
+        ];
+        let err = solve(&nodes, Arrangement::Horizontal, Align::Top)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Cyclic"), "{}", err);
+    }
+
+    #[test]
+    fn overlap() {
+        let nodes = [
+            node("a", (100, 100), Placement::Fixed(0, 0)),
+            node("b", (100, 100), Placement::Fixed(50, 99)),
+        ];
+        assert!(solve(&nodes, Arrangement::Horizontal, Align::Top).is_err());
+    }
+
+    #[test]
+    fn oversized() {
+        let nodes = [
+            node("a", (i32::MAX, i32::MAX), Placement::Auto),
+            node("b", (100, 100), Placement::Auto),
+        ];
+        for arrangement in [Arrangement::Horizontal, Arrangement::Vertical] {
+            let err = solve(&nodes, arrangement, Align::Top).unwrap_err();
+            assert!(err.to_string().starts_with("Outputs exceed"), "{}", err);
+        }
+        let nodes = [
+            node("a", (i32::MAX, i32::MAX), Placement::Fixed(0, 0)),
+            node("b", (100, 100), Placement::Fixed(10, 10)),
+        ];
+        assert!(solve(&nodes, Arrangement::Horizontal, Align::Top).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+mod common;
+
+use std::process::Stdio;
+
+use common::{FakeOutput, FakeSway};
+
+const LAPTOP: (&str, &str) = ("LG Display", "0x058B");
+const DELL: (&str, &str) = ("Dell Inc.", "DELL P2723DE");
+
+fn outputs() -> Vec<FakeOutput> {
+    vec![
+        FakeOutput::new("eDP-1", LAPTOP.0, LAPTOP.1, &[(1920, 1200, 60000)]).active_at(0, 0),
+        FakeOutput::new(
+            "DP-1",
+            DELL.0,
+            DELL.1,
+            &[(1920, 1080, 60000), (2560, 1440, 59951)],
+        ),
+    ]
+}
+
+const CFG: &str = r#"
+[alone]
+outputs = [{ name = "LG Display 0x058B", scale = 1.5 }]
+
+[home]
+outputs = [
+    { name = "Dell Inc. DELL P2723DE", scale = 1.0 },
+    { name = "LG Display 0x058B", scale = 1.5 },
+]
+"#;
+
+#[test]
+fn display_lists_outputs() {
+    let sway = FakeSway::start(outputs());
+    let out = sway.oswo(&["display"]);
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("eDP-1"), "{}", stdout);
+    assert!(stdout.contains("Dell Inc. DELL P2723DE"), "{}", stdout);
+}
+
+#[test]
+fn set_by_connector() {
+    let sway = FakeSway::start(outputs());
+    let out = sway.oswo(&["set", "DP-1", "eDP-1"]);
+    assert!(out.status.success(), "{:?}", out);
+    assert_eq!(
+        sway.output_commands(),
+        [
+            "output DP-1 enable position 0 0 resolution 2560x1440 scale 1 transform normal",
+            "output eDP-1 enable position 2560 0 resolution 1920x1200 scale 1 transform normal",
+        ]
+    );
+}
+
+#[test]
+fn use_config() {
+    let sway = FakeSway::start(outputs());
+    let cfg = sway.write_config(CFG);
+    let out = sway.oswo(&["use", "home", "-c", cfg.to_str().unwrap()]);
+    assert!(out.status.success(), "{:?}", out);
+    assert_eq!(
+        sway.output_commands(),
+        [
+            "output DP-1 enable position 0 0 resolution 2560x1440 scale 1 transform normal",
+            "output eDP-1 enable position 2560 0 resolution 1920x1200 scale 1.5 transform normal",
+        ]
+    );
+    let state = sway.outputs();
+    assert!(state.iter().all(|o| o.active));
+    assert_eq!(state[0].position, (2560, 0));
+}
+
+#[test]
+fn use_unknown_config_fails() {
+    let sway = FakeSway::start(outputs());
+    let cfg = sway.write_config(CFG);
+    let out = sway.oswo(&["use", "office", "-c", cfg.to_str().unwrap()]);
+    assert!(!out.status.success());
+    assert!(sway.output_commands().is_empty());
+}
+
+#[test]
+fn use_dry_run() {
+    let sway = FakeSway::start(outputs());
+    let cfg = sway.write_config(CFG);
+    let out = sway.oswo(&["--dry-run", "use", "home", "-c", cfg.to_str().unwrap()]);
+    assert!(out.status.success(), "{:?}", out);
+    assert!(sway.commands().is_empty());
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout),
+        "output DP-1 enable position 0 0 resolution 2560x1440 scale 1 transform normal\n\
+         output eDP-1 enable position 2560 0 resolution 1920x1200 scale 1.5 transform normal\n"
+    );
+}
+
+#[test]
+fn use_rolls_back_on_failure() {
+    let sway = FakeSway::start(outputs());
+    sway.fail_commands_containing("output eDP-1 enable position 2560");
+    let cfg = sway.write_config(CFG);
+    let out = sway.oswo(&["use", "home", "-c", cfg.to_str().unwrap()]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("Restored the previous output configuration"),
+        "{}",
+        stderr
+    );
+    assert_eq!(
+        sway.output_commands()[2..],
+        [
+            "output eDP-1 enable position 0 0 resolution 1920x1200@60.000Hz scale 1 transform normal",
+            "output DP-1 disable",
+        ]
+    );
+    let state = sway.outputs();
+    assert!(!state[1].active);
+}
+
+#[test]
+fn use_reverts_without_confirmation() {
+    let sway = FakeSway::start(outputs());
+    let cfg = sway.write_config(CFG);
+    let out = sway.oswo(&["use", "home", "-c", cfg.to_str().unwrap(), "--confirm", "1"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("not confirmed"), "{}", stderr);
+    assert_eq!(
+        sway.output_commands()[2..],
+        [
+            "output eDP-1 enable position 0 0 resolution 1920x1200@60.000Hz scale 1 transform normal",
+            "output DP-1 disable",
+        ]
+    );
+    let state = sway.outputs();
+    assert!(!state[1].active);
+}
+
+#[test]
+fn use_keeps_confirmed_setup() {
+    let sway = FakeSway::start(outputs());
+    let cfg = sway.write_config(CFG);
+    let mut child = sway
+        .command(env!("CARGO_BIN_EXE_oswo"))
+        .args([
+            "use",
+            "home",
+            "-c",
+            cfg.to_str().unwrap(),
+            "--confirm",
+            "10",
+        ])
+        .stdin(Stdio::null())
+        .spawn()
+        .unwrap();
+    sway.wait_for(|c| c.len() == 2);
+    assert!(sway.oswo(&["confirm"]).status.success());
+    assert!(child.wait().unwrap().success());
+    assert_eq!(sway.output_commands().len(), 2);
+    assert!(sway.outputs().iter().all(|o| o.active));
+}
+
+#[test]
+fn set_unknown_output_fails() {
+    let sway = FakeSway::start(outputs());
+    let out = sway.oswo(&["set", "DP-7"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("Display 'DP-7' is not connected"),
+        "{}",
+        stderr
+    );
+    assert!(sway.output_commands().is_empty());
+}
+
+#[test]
+fn set_nothing_requires_force() {
+    let sway = FakeSway::start(outputs());
+    let out = sway.oswo(&["set"]);
+    assert!(!out.status.success());
+    assert!(sway.output_commands().is_empty());
+
+    let out = sway.oswo(&["set", "--force"]);
+    assert!(out.status.success(), "{:?}", out);
+    assert!(sway.outputs().iter().all(|o| !o.active));
+}
+
+#[test]
+fn use_twice_is_idempotent() {
+    let sway = FakeSway::start(outputs());
+    let cfg = sway.write_config(CFG);
+    let out = sway.oswo(&["use", "home", "-c", cfg.to_str().unwrap()]);
+    assert!(out.status.success(), "{:?}", out);
+    sway.clear_commands();
+    let out = sway.oswo(&["use", "home", "-c", cfg.to_str().unwrap()]);
+    assert!(out.status.success(), "{:?}", out);
+    assert!(sway.output_commands().is_empty());
+}
+
+#[test]
+fn diff_config() {
+    let sway = FakeSway::start(outputs());
+    let cfg = sway.write_config(CFG);
+    let out = sway.oswo(&["diff", "alone", "-c", cfg.to_str().unwrap()]);
+    assert!(out.status.success(), "{:?}", out);
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout),
+        "eDP-1 (LG Display 0x058B)\n  scale: 1 -> 1.5\n\
+         DP-1 (Dell Inc. DELL P2723DE): unchanged\n"
+    );
+    assert!(sway.commands().is_empty());
+}
+
+#[test]
+fn display_json() {
+    let sway = FakeSway::start(outputs());
+    let out = sway.oswo(&["display", "--json"]);
+    assert!(out.status.success(), "{:?}", out);
+    let json: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let laptop = &json[1];
+    assert_eq!(laptop["name"], "eDP-1");
+    assert_eq!(laptop["model"], "LG Display 0x058B");
+    assert_eq!(laptop["position"], serde_json::json!({ "x": 0, "y": 0 }));
+    assert_eq!(laptop["mode"]["width"], 1920);
+    assert_eq!(laptop["scale"], 1.0);
+    assert_eq!(laptop["enabled"], true);
+    assert_eq!(json[0]["modes"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn print_json() {
+    let sway = FakeSway::start(outputs());
+    let cfg = sway.write_config(CFG);
+    let out = sway.oswo(&["print", "--json", "-c", cfg.to_str().unwrap()]);
+    assert!(out.status.success(), "{:?}", out);
+    let json: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(
+        json["home"]["outputs"][1],
+        serde_json::json!({ "name": "LG Display 0x058B", "scale": 1.5 })
+    );
+    assert_eq!(json["alone"]["priority"], serde_json::Value::Null);
+}
+
+#[test]
+fn diff_json() {
+    let sway = FakeSway::start(outputs());
+    let cfg = sway.write_config(CFG);
+    let out = sway.oswo(&["diff", "alone", "--json", "-c", cfg.to_str().unwrap()]);
+    assert!(out.status.success(), "{:?}", out);
+    let json: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!([
+            {
+                "name": "eDP-1",
+                "model": "LG Display 0x058B",
+                "changes": [{ "property": "scale", "current": 1.0, "planned": 1.5 }],
+            },
+            { "name": "DP-1", "model": "Dell Inc. DELL P2723DE", "changes": [] },
+        ])
+    );
+}
+
+#[test]
+fn explain_ranks_configs() {
+    let sway = FakeSway::start(outputs());
+    let cfg = sway.write_config(CFG);
+    let out = sway.oswo(&["explain", "-c", cfg.to_str().unwrap()]);
+    assert!(out.status.success(), "{:?}", out);
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout),
+        "1. home (priority: 0, outputs: 2, exact: 2)\n\
+         2. alone (priority: 0, outputs: 1, exact: 1)\n"
+    );
+    assert!(sway.commands().is_empty());
+}
+
+#[test]
+fn confirm_without_sway() {
+    let sway = FakeSway::start(outputs());
+    let out = sway
+        .command(env!("CARGO_BIN_EXE_oswo"))
+        .env("SWAYSOCK", sway.dir().join("missing.sock"))
+        .arg("confirm")
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{:?}", out);
+    assert!(sway.dir().join("oswo.confirm").exists());
+}
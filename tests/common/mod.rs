@@ -0,0 +1,378 @@
+//! A fake sway instance speaking the i3/sway IPC protocol on a Unix socket.
+//!
+//! Only the messages oswo relies on are implemented: `RUN_COMMAND`, `GET_OUTPUTS` and
+//! `SUBSCRIBE` for output events. Output commands are recorded and applied to the fake state,
+//! hotplug events can be scripted by replacing the outputs and emitting an output event.
+
+#![allow(dead_code)]
+
+use std::{
+    io::{Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    process::{Child, Command, Output},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const RUN_COMMAND: u32 = 0;
+const SUBSCRIBE: u32 = 2;
+const GET_OUTPUTS: u32 = 3;
+const OUTPUT_EVENT: u32 = 0x8000_0001;
+
+/// An output as reported by the fake sway.
+#[derive(Debug, Clone)]
+pub struct FakeOutput {
+    pub name: String,
+    pub make: String,
+    pub model: String,
+    pub serial: String,
+    pub active: bool,
+    pub position: (i32, i32),
+    /// Current mode as width, height and refresh in mHz.
+    pub current_mode: Option<(i32, i32, i32)>,
+    pub scale: f64,
+    pub transform: String,
+    /// Available modes as width, height and refresh in mHz.
+    pub modes: Vec<(i32, i32, i32)>,
+}
+
+impl FakeOutput {
+    pub fn new(name: &str, make: &str, model: &str, modes: &[(i32, i32, i32)]) -> Self {
+        Self {
+            name: name.to_string(),
+            make: make.to_string(),
+            model: model.to_string(),
+            serial: String::new(),
+            active: false,
+            position: (0, 0),
+            current_mode: None,
+            scale: 1.0,
+            transform: "normal".to_string(),
+            modes: modes.to_vec(),
+        }
+    }
+
+    pub fn serial(self, serial: &str) -> Self {
+        Self {
+            serial: serial.to_string(),
+            ..self
+        }
+    }
+
+    /// Mark the output active in its first mode at the given position.
+    pub fn active_at(self, x: i32, y: i32) -> Self {
+        Self {
+            active: true,
+            position: (x, y),
+            current_mode: self.modes.first().copied(),
+            ..self
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let mode = |(width, height, refresh): (i32, i32, i32)| json!({ "width": width, "height": height, "refresh": refresh });
+        let (width, height) = self
+            .current_mode
+            .filter(|_| self.active)
+            .map(|(w, h, _)| (w, h))
+            .unwrap_or((0, 0));
+        json!({
+            "id": if self.active { Some(1) } else { None },
+            "name": self.name,
+            "make": self.make,
+            "model": self.model,
+            "serial": self.serial,
+            "active": self.active,
+            "dpms": self.active,
+            "primary": false,
+            "scale": if self.active { self.scale } else { -1.0 },
+            "subpixel_hinting": "unknown",
+            "transform": self.transform,
+            "current_workspace": null,
+            "modes": self.modes.iter().copied().map(mode).collect::<Vec<_>>(),
+            "current_mode": self.current_mode.filter(|_| self.active).map(mode),
+            "rect": { "x": self.position.0, "y": self.position.1, "width": width, "height": height },
+            "focus": [],
+            "focused": false,
+        })
+    }
+
+    /// Apply the arguments of an `output <name> ...` command.
+    fn apply(&mut self, args: &[&str]) {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match *arg {
+                "enable" => self.active = true,
+                "disable" => self.active = false,
+                "position" | "pos" => {
+                    let x = args.next().and_then(|x| x.parse().ok()).unwrap_or(0);
+                    let y = args.next().and_then(|y| y.parse().ok()).unwrap_or(0);
+                    self.position = (x, y);
+                }
+                "resolution" | "res" | "mode" => {
+                    let mode = args.next().unwrap_or(&"");
+                    let (size, refresh) = mode.split_once('@').unwrap_or((mode, ""));
+                    let (w, h) = size.split_once('x').unwrap_or(("0", "0"));
+                    let (w, h) = (w.parse().unwrap_or(0), h.parse().unwrap_or(0));
+                    let refresh = refresh
+                        .trim_end_matches("Hz")
+                        .parse::<f64>()
+                        .map(|r| (r * 1000.0).round() as i32)
+                        .ok();
+                    self.current_mode = self
+                        .modes
+                        .iter()
+                        .filter(|m| m.0 == w && m.1 == h)
+                        .find(|m| refresh.is_none_or(|r| (m.2 - r).abs() < 1000))
+                        .copied()
+                        .or(Some((w, h, 60000)));
+                }
+                "scale" => self.scale = args.next().and_then(|s| s.parse().ok()).unwrap_or(1.0),
+                "transform" => self.transform = args.next().unwrap_or(&"normal").to_string(),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    outputs: Vec<FakeOutput>,
+    commands: Vec<String>,
+    failing: Vec<String>,
+    subscribers: Vec<UnixStream>,
+}
+
+/// A fake sway listening on a socket in a fresh temporary directory.
+pub struct FakeSway {
+    dir: PathBuf,
+    socket: PathBuf,
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeSway {
+    pub fn start(outputs: Vec<FakeOutput>) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "oswo-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        let socket = dir.join("sway-ipc.sock");
+        let listener = UnixListener::bind(&socket).expect("bind fake sway socket");
+
+        let state = Arc::new(Mutex::new(State {
+            outputs,
+            ..Default::default()
+        }));
+        let accept_state = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { break };
+                let state = accept_state.clone();
+                thread::spawn(move || handle_client(stream, state));
+            }
+        });
+
+        Self { dir, socket, state }
+    }
+
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    /// The temporary directory owned by this instance, e.g. for config files.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Write a config file into the temporary directory and return its path.
+    pub fn write_config(&self, content: &str) -> PathBuf {
+        let path = self.dir.join("oswo.toml");
+        std::fs::write(&path, content).expect("write config");
+        path
+    }
+
+    /// All commands received so far, in order.
+    pub fn commands(&self) -> Vec<String> {
+        self.state.lock().unwrap().commands.clone()
+    }
+
+    /// Only the `output ...` commands received so far, in order.
+    pub fn output_commands(&self) -> Vec<String> {
+        self.commands()
+            .into_iter()
+            .filter(|c| c.starts_with("output "))
+            .collect()
+    }
+
+    pub fn clear_commands(&self) {
+        self.state.lock().unwrap().commands.clear();
+    }
+
+    pub fn outputs(&self) -> Vec<FakeOutput> {
+        self.state.lock().unwrap().outputs.clone()
+    }
+
+    /// Let every command containing `pattern` fail.
+    pub fn fail_commands_containing(&self, pattern: &str) {
+        self.state.lock().unwrap().failing.push(pattern.to_string());
+    }
+
+    /// Replace the connected outputs and notify subscribers, like plugging or unplugging a display.
+    pub fn hotplug(&self, outputs: Vec<FakeOutput>) {
+        let mut state = self.state.lock().unwrap();
+        state.outputs = outputs;
+        let payload = json!({ "change": "unspecified" }).to_string();
+        state
+            .subscribers
+            .retain_mut(|s| write_message(s, OUTPUT_EVENT, payload.as_bytes()).is_ok());
+    }
+
+    /// Number of clients subscribed to output events.
+    pub fn subscribers(&self) -> usize {
+        self.state.lock().unwrap().subscribers.len()
+    }
+
+    /// A command for the given binary talking to this instance.
+    pub fn command(&self, bin: &str) -> Command {
+        let mut cmd = Command::new(bin);
+        cmd.env("SWAYSOCK", &self.socket)
+            .env_remove("I3SOCK")
+            .env("XDG_RUNTIME_DIR", &self.dir);
+        cmd
+    }
+
+    /// Run `oswo` with the given arguments against this instance.
+    pub fn oswo(&self, args: &[&str]) -> Output {
+        self.command(env!("CARGO_BIN_EXE_oswo"))
+            .args(args)
+            .output()
+            .expect("run oswo")
+    }
+
+    /// Spawn `oswod` with the given arguments against this instance.
+    pub fn oswod(&self, args: &[&str]) -> Daemon {
+        let child = self
+            .command(env!("CARGO_BIN_EXE_oswod"))
+            .args(args)
+            .spawn()
+            .expect("spawn oswod");
+        Daemon(child)
+    }
+
+    /// Wait until `cond` holds for the received commands, panicking after a timeout.
+    pub fn wait_for(&self, cond: impl Fn(&[String]) -> bool) -> Vec<String> {
+        let start = Instant::now();
+        loop {
+            let commands = self.output_commands();
+            if cond(&commands) {
+                return commands;
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "timed out waiting for commands, got {:?}",
+                commands
+            );
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+impl Drop for FakeSway {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A running `oswod` that is killed when dropped.
+pub struct Daemon(Child);
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn read_message(stream: &mut UnixStream) -> std::io::Result<(u32, Vec<u8>)> {
+    let mut header = [0_u8; 14];
+    stream.read_exact(&mut header)?;
+    if &header[..6] != MAGIC {
+        return Err(std::io::Error::other("invalid magic"));
+    }
+    let len = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let ty = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+    let mut payload = vec![0_u8; len as usize];
+    stream.read_exact(&mut payload)?;
+    Ok((ty, payload))
+}
+
+fn write_message(stream: &mut UnixStream, ty: u32, payload: &[u8]) -> std::io::Result<()> {
+    let mut msg = Vec::with_capacity(14 + payload.len());
+    msg.extend_from_slice(MAGIC);
+    msg.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    msg.extend_from_slice(&ty.to_ne_bytes());
+    msg.extend_from_slice(payload);
+    stream.write_all(&msg)
+}
+
+fn handle_client(mut stream: UnixStream, state: Arc<Mutex<State>>) {
+    while let Ok((ty, payload)) = read_message(&mut stream) {
+        let payload = String::from_utf8_lossy(&payload).to_string();
+        let mut state = state.lock().unwrap();
+        let reply = match ty {
+            RUN_COMMAND => {
+                let outcomes: Vec<Value> = payload
+                    .split([';', '\n'])
+                    .map(str::trim)
+                    .filter(|c| !c.is_empty())
+                    .map(|cmd| run_command(&mut state, cmd))
+                    .collect();
+                Value::Array(outcomes)
+            }
+            GET_OUTPUTS => Value::Array(state.outputs.iter().map(FakeOutput::to_json).collect()),
+            SUBSCRIBE => {
+                let reply = json!({ "success": true }).to_string();
+                if write_message(&mut stream, ty, reply.as_bytes()).is_ok() {
+                    if let Ok(s) = stream.try_clone() {
+                        state.subscribers.push(s);
+                    }
+                }
+                continue;
+            }
+            _ => json!({ "success": false, "error": "unsupported message type" }),
+        };
+        if write_message(&mut stream, ty, reply.to_string().as_bytes()).is_err() {
+            break;
+        }
+    }
+}
+
+fn run_command(state: &mut State, cmd: &str) -> Value {
+    state.commands.push(cmd.to_string());
+    if state.failing.iter().any(|p| cmd.contains(p.as_str())) {
+        return json!({ "success": false, "parse_error": false, "error": "scripted failure" });
+    }
+    let args: Vec<&str> = cmd.split_whitespace().collect();
+    match args.as_slice() {
+        ["output", name, rest @ ..] => match state.outputs.iter_mut().find(|o| o.name == *name) {
+            Some(output) => {
+                output.apply(rest);
+                json!({ "success": true })
+            }
+            None => json!({ "success": false, "parse_error": false, "error": "unknown output" }),
+        },
+        _ => json!({ "success": true }),
+    }
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::{FakeOutput, FakeSway};
+
+fn laptop() -> FakeOutput {
+    FakeOutput::new("eDP-1", "LG Display", "0x058B", &[(1920, 1200, 60000)]).active_at(0, 0)
+}
+
+fn dell() -> FakeOutput {
+    FakeOutput::new("DP-1", "Dell Inc.", "DELL P2723DE", &[(2560, 1440, 59951)])
+}
+
+const CFG: &str = r#"
+[alone]
+outputs = [{ name = "LG Display 0x058B", scale = 1.5 }]
+
+[home]
+priority = 1
+outputs = [
+    { name = "LG Display 0x058B", scale = 1.5 },
+    { name = "Dell Inc. DELL P2723DE" },
+]
+"#;
+
+#[test]
+fn activates_config_on_hotplug() {
+    let sway = FakeSway::start(vec![laptop()]);
+    let cfg = sway.write_config(CFG);
+    let _daemon = sway.oswod(&["-c", cfg.to_str().unwrap()]);
+
+    let commands = sway.wait_for(|c| !c.is_empty());
+    assert_eq!(
+        commands,
+        ["output eDP-1 enable position 0 0 resolution 1920x1200 scale 1.5 transform normal"]
+    );
+
+    sway.clear_commands();
+    sway.hotplug(vec![laptop(), dell()]);
+    let commands = sway.wait_for(|c| c.len() >= 2);
+    assert_eq!(
+        commands,
+        [
+            "output eDP-1 enable position 0 0 resolution 1920x1200 scale 1.5 transform normal",
+            "output DP-1 enable position 1280 0 resolution 2560x1440 scale 1 transform normal",
+        ]
+    );
+
+    sway.clear_commands();
+    sway.hotplug(vec![laptop()]);
+    let commands = sway.wait_for(|c| !c.is_empty());
+    assert_eq!(
+        commands,
+        ["output eDP-1 enable position 0 0 resolution 1920x1200 scale 1.5 transform normal"]
+    );
+}
+
+#[test]
+fn activates_config_by_connector() {
+    let projector = FakeOutput::new(
+        "HDMI-A-1",
+        "Unknown",
+        "Projector 3000",
+        &[(1920, 1080, 60000)],
+    );
+    let sway = FakeSway::start(vec![laptop()]);
+    let cfg = sway.write_config(&format!(
+        "{}\n[meeting]\npriority = 2\noutputs = [\"LG Display 0x058B\", {{ connector = \"HDMI-A-1\", above = \"LG Display 0x058B\" }}]\n",
+        CFG
+    ));
+    let _daemon = sway.oswod(&["-c", cfg.to_str().unwrap()]);
+    sway.wait_for(|c| !c.is_empty());
+
+    sway.clear_commands();
+    sway.hotplug(vec![laptop(), projector]);
+    let commands = sway.wait_for(|c| c.len() >= 2);
+    assert_eq!(
+        commands,
+        [
+            "output eDP-1 enable position 0 1080 resolution 1920x1200 scale 1 transform normal",
+            "output HDMI-A-1 enable position 0 0 resolution 1920x1080 scale 1 transform normal",
+        ]
+    );
+}
+
+#[test]
+fn survives_failing_config() {
+    let sway = FakeSway::start(vec![laptop()]);
+    let cfg = sway.write_config(CFG);
+    let _daemon = sway.oswod(&["-c", cfg.to_str().unwrap()]);
+    sway.wait_for(|c| !c.is_empty());
+
+    sway.fail_commands_containing("output DP-1 enable");
+    sway.clear_commands();
+    sway.hotplug(vec![laptop(), dell()]);
+    sway.wait_for(|c| c.iter().any(|c| c.starts_with("output DP-1 enable")));
+
+    sway.clear_commands();
+    sway.hotplug(vec![laptop()]);
+    let commands = sway.wait_for(|c| !c.is_empty());
+    assert!(
+        commands[0].starts_with("output eDP-1 enable"),
+        "{:?}",
+        commands
+    );
+}
+
+#[test]
+fn dry_run_prints_and_exits() {
+    let sway = FakeSway::start(vec![laptop(), dell()]);
+    let cfg = sway.write_config(CFG);
+    let out = sway
+        .command(env!("CARGO_BIN_EXE_oswod"))
+        .args(["--dry-run", "-c", cfg.to_str().unwrap()])
+        .output()
+        .expect("run oswod");
+    assert!(out.status.success(), "{:?}", out);
+    assert!(sway.commands().is_empty());
+    assert_eq!(sway.subscribers(), 0);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert_eq!(stdout.lines().count(), 2, "{}", stdout);
+    assert!(
+        stdout.starts_with("output eDP-1 enable position 0 0"),
+        "{}",
+        stdout
+    );
+}